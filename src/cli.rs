@@ -0,0 +1,266 @@
+use std::path::Path;
+
+use clap::{error::ErrorKind, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use globset::Glob;
+
+use crate::languages_mapping::LANGUAGES;
+
+// Exit codes, so that scripts can tell a failed run from a run that found nothing
+pub const EXIT_SUCCESS: u8 = 0;
+pub const EXIT_NOTHING_FOUND: u8 = 1;
+pub const EXIT_ERROR: u8 = 2; // same code clap uses for usage errors
+
+#[derive(Parser)]
+#[command(
+    name = "mop",
+    version,
+    about = "Search in your files, count lines and display the todo items you have",
+    after_help = "Exit status: 0 if something was found, 1 if nothing was found, 2 on error \
+        (including a path that can't be read, with --strict).",
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+impl Cli {
+    // clap gives the first positional argument to the pattern of the search
+    // subcommand even with -e or -f, it is moved back to the paths
+    pub fn parse_args() -> Cli {
+        let mut cli = Cli::parse();
+        if let Command::Search(args) = &mut cli.command {
+            if !args.regex.is_empty() || args.patterns_file.is_some() {
+                if let Some(path) = args.pattern.take() {
+                    if let Err(error) = existing_path(&path) {
+                        Cli::command().error(ErrorKind::ValueValidation, error).exit();
+                    }
+                    args.files.paths.insert(0, path);
+                }
+            }
+            if args.files.paths.is_empty() {
+                Cli::command().error(ErrorKind::MissingRequiredArgument, "no PATH was given to search in").exit();
+            }
+        }
+        cli
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Display the number of files, blank, comment and code lines per language
+    #[command(short_flag = 'm', long_flag = "metric")]
+    Metric(MetricArgs),
+
+    /// Search for a pattern in the given files and directories
+    #[command(short_flag = 's', long_flag = "search")]
+    Search(SearchArgs),
+
+    /// Display the TODO comments of the given files and directories
+    #[command(short_flag = 't', long_flag = "todo")]
+    Todo(TodoArgs),
+}
+
+// Options shared by every subcommand to choose which files are looked at
+#[derive(Args)]
+pub struct FilesArgs {
+    /// Files or directories to look into
+    #[arg(required = true, value_name = "PATH", value_parser = existing_path)]
+    pub paths: Vec<String>,
+
+    /// Don't respect .gitignore, .ignore and .mopignore files
+    #[arg(long)]
+    pub no_ignore: bool,
+
+    /// Only look into the files matching this glob (can be repeated)
+    #[arg(long, value_name = "GLOB", value_parser = valid_glob)]
+    pub include: Vec<String>,
+
+    /// Skip the files matching this glob (can be repeated)
+    #[arg(long, value_name = "GLOB", value_parser = valid_glob)]
+    pub exclude: Vec<String>,
+
+    /// Only look into the files of these languages (comma separated, can be repeated)
+    #[arg(long, value_name = "LANGUAGE", value_delimiter = ',', value_parser = known_language)]
+    pub lang: Vec<String>,
+
+    /// Print the language of each file and the rule that detected it
+    #[arg(long)]
+    pub verbose: bool,
+
+    /// Fail with exit status 2 when a path can't be read, instead of skipping it
+    #[arg(long)]
+    pub strict: bool,
+}
+
+#[derive(Args)]
+pub struct MetricArgs {
+    #[command(flatten)]
+    pub files: FilesArgs,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+
+    /// Report the statistics of each file
+    #[arg(long)]
+    pub by_file: bool,
+
+    /// Report the statistics rolled up by directory, down to DEPTH directories below the root (--by-dir=DEPTH) [default: no limit]
+    #[arg(long, value_name = "DEPTH", num_args = 0..=1, require_equals = true, conflicts_with = "by_file")]
+    pub by_dir: Option<Option<usize>>,
+
+    /// Column to sort the languages, the files or the directories by, numbers are sorted from the biggest
+    #[arg(long, value_enum, value_name = "COLUMN", default_value_t = SortColumn::Total)]
+    pub sort: SortColumn,
+
+    /// Reverse the sort order
+    #[arg(long)]
+    pub reverse: bool,
+
+    /// How to count the lines with both code and a comment, like `foo(); // why`. Their number is only in the json output
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = MixedLines::Code)]
+    pub mixed: MixedLines,
+
+    /// Only report the first N files
+    #[arg(long, value_name = "N", requires = "by_file")]
+    pub top: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortColumn {
+    /// Name of the language or path of the file
+    Name,
+    Language,
+    Files,
+    Size,
+    Blank,
+    Comment,
+    Docs,
+    Code,
+    Total,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MixedLines {
+    /// Count them as code lines, like cloc and tokei
+    Code,
+    /// Count them as comment lines
+    Comment,
+    /// Count them as both code and comment lines, the columns then add up to more than the total
+    Both,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Table for the terminal
+    Table,
+    /// JSON document, see the README for the schema
+    Json,
+    /// Comma separated values, with a header line
+    Csv,
+    /// Markdown table
+    Markdown,
+}
+
+// With -e or -f, every positional argument is a path, see Cli::parse_args
+#[derive(Args)]
+#[command(mut_arg("paths", |arg| arg.required(false).required_unless_present_any(["regex", "patterns_file"])))]
+pub struct SearchArgs {
+    /// String to search for, taken literally
+    #[arg(value_parser = non_empty, required_unless_present_any = ["regex", "patterns_file"])]
+    pub pattern: Option<String>,
+
+    /// Regular expression to search for, instead of PATTERN (can be repeated, a line
+    /// matching any of them)
+    #[arg(short = 'e', long, value_name = "REGEX", value_parser = non_empty)]
+    pub regex: Vec<String>,
+
+    /// Read the regular expressions to search for from FILE, one per line
+    #[arg(short = 'f', long = "file", value_name = "FILE")]
+    pub patterns_file: Option<String>,
+
+    /// Only keep the lines also matching this pattern (can be repeated)
+    #[arg(long, value_name = "PATTERN", value_parser = non_empty)]
+    pub and: Vec<String>,
+
+    /// Drop the lines matching this pattern (can be repeated)
+    #[arg(long, value_name = "PATTERN", value_parser = non_empty)]
+    pub not: Vec<String>,
+
+    /// Take the patterns of -e, -f, --and and --not literally too
+    #[arg(short = 'F', long)]
+    pub fixed_strings: bool,
+
+    /// Ignore the case of the letters
+    #[arg(short = 'i', long, overrides_with = "smart_case")]
+    pub ignore_case: bool,
+
+    /// Ignore the case of the letters, unless the pattern has an uppercase letter
+    #[arg(short = 'S', long, overrides_with = "ignore_case")]
+    pub smart_case: bool,
+
+    /// Only match whole words, with no word character right before or after the match
+    #[arg(short = 'w', long)]
+    pub word: bool,
+
+    /// Match the patterns against the whole file, so that they can span several lines
+    #[arg(short = 'U', long)]
+    pub multiline: bool,
+
+    /// Show N lines after each match
+    #[arg(short = 'A', long, value_name = "N")]
+    pub after_context: Option<usize>,
+
+    /// Show N lines before each match
+    #[arg(short = 'B', long, value_name = "N")]
+    pub before_context: Option<usize>,
+
+    /// Show N lines before and after each match, unless -A or -B is given
+    #[arg(short = 'C', long, value_name = "N")]
+    pub context: Option<usize>,
+
+    #[command(flatten)]
+    pub files: FilesArgs,
+}
+
+#[derive(Args)]
+pub struct TodoArgs {
+    #[command(flatten)]
+    pub files: FilesArgs,
+}
+
+fn existing_path(arg: &str) -> Result<String, String> {
+    match Path::new(arg).exists() {
+        true => Ok(arg.to_string()),
+        false => Err(format!("{} is not a file or directory", arg)),
+    }
+}
+
+fn non_empty(arg: &str) -> Result<String, String> {
+    match arg.is_empty() {
+        true => Err("the pattern can not be empty".to_string()),
+        false => Ok(arg.to_string()),
+    }
+}
+
+fn valid_glob(arg: &str) -> Result<String, String> {
+    match Glob::new(arg) {
+        Ok(_) => Ok(arg.to_string()),
+        Err(e) => Err(e.kind().to_string()),
+    }
+}
+
+fn known_language(arg: &str) -> Result<String, String> {
+    let arg = arg.trim();
+    if arg.eq_ignore_ascii_case("Unknown") {
+        return Ok("Unknown".to_string());
+    }
+    match LANGUAGES.keys().find(|language| language.eq_ignore_ascii_case(arg)) {
+        Some(language) => Ok(language.to_string()),
+        None => {
+            let mut languages = LANGUAGES.keys().map(String::as_str).collect::<Vec<_>>();
+            languages.sort_unstable();
+            Err(format!("unknown language, expected one of: {}, Unknown", languages.join(", ")))
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::path::{Component, Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{
+    cli::SortColumn,
+    file_handler::FileStats,
+    file_supplier::{csv_field, format_table},
+};
+
+// Statistics of the files rolled up by directory. The root is the deepest directory
+// containing every file, and each directory also counts the files of its subdirectories
+pub struct DirectoryTree {
+    root: Directory,
+}
+
+#[derive(Default)]
+struct Directory {
+    stat: DirectoryStat,
+    // in the order of the --sort column, by name by default
+    children: Vec<(String, Directory)>,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct DirectoryStat {
+    path: String,
+    files: usize,
+    size: usize,
+    blank: usize,
+    comment: usize,
+    docs: usize,
+    code: usize,
+    mixed: usize,
+    lines: usize,
+}
+
+impl DirectoryStat {
+    fn add_file_stat(&mut self, file_stat: &FileStats) {
+        if file_stat.get_embedded_in().is_none() {
+            self.files += 1;
+        }
+        self.size += file_stat.get_size();
+        self.blank += file_stat.get_blank_lines();
+        self.comment += file_stat.get_comment_lines();
+        self.docs += file_stat.get_doc_lines();
+        self.code += file_stat.get_code_lines();
+        self.mixed += file_stat.get_mixed_lines();
+        self.lines += file_stat.get_lines();
+    }
+}
+
+impl DirectoryTree {
+    // Files deeper than depth directories below the root are counted in their ancestor at that depth
+    pub fn new(file_stats: &[FileStats], depth: usize) -> DirectoryTree {
+        let directories = file_stats.iter().map(|file_stat| parent_components(file_stat.get_path())).collect::<Vec<_>>();
+        let common = common_prefix(&directories);
+
+        let mut root = Directory::default();
+        root.stat.path = display_path(&common);
+
+        for (file_stat, components) in file_stats.iter().zip(directories.iter()) {
+            let mut directory = &mut root;
+            directory.stat.add_file_stat(file_stat);
+            for (index, component) in components.iter().enumerate().skip(common.len()).take(depth) {
+                let child = match directory.children.iter().position(|(name, _)| name == component) {
+                    Some(child) => child,
+                    None => {
+                        directory.children.push((component.to_string(), Directory::default()));
+                        directory.children.len() - 1
+                    }
+                };
+                directory = &mut directory.children[child].1;
+                if directory.stat.path.is_empty() {
+                    directory.stat.path = display_path(&components[..=index]);
+                }
+                directory.stat.add_file_stat(file_stat);
+            }
+        }
+
+        root.sort(SortColumn::Name, false);
+        DirectoryTree { root }
+    }
+
+    // The subdirectories of each directory are sorted like the languages
+    pub fn sort(&mut self, column: SortColumn, reverse: bool) {
+        self.root.sort(column, reverse);
+    }
+
+    // Every directory, parents before their children
+    pub fn directories(&self) -> Vec<&DirectoryStat> {
+        let mut directories = Vec::new();
+        self.root.collect(&mut directories);
+        directories
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut s = String::from("directory,files,size,blank,comment,docs,code,total\n");
+        for stat in self.directories() {
+            s += &format!("{},{},{},{},{},{},{},{}\n", csv_field(&stat.path), stat.files, stat.size,
+                stat.blank, stat.comment, stat.docs, stat.code, stat.lines);
+        }
+        s
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut s = String::from("| Directory | Files | Size | Blank | Comment | Docs | Code | Total |\n");
+        s += "|:----------|------:|-----:|------:|--------:|-----:|-----:|------:|\n";
+        for stat in self.directories() {
+            s += &format!("| {} | {} | {} | {} | {} | {} | {} | {} |\n", stat.path.replace('|', "\\|"), stat.files,
+                stat.size, stat.blank, stat.comment, stat.docs, stat.code, stat.lines);
+        }
+        s
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut rows = vec![["Directory", "Files", "Size", "Blank lines", "Comment lines", "Doc lines", "Code lines", "TOTAL"]
+            .map(String::from).to_vec()];
+        self.root.tree_rows(self.root.stat.path.clone(), "", &mut rows);
+        format_table(&rows, 1, false)
+    }
+}
+
+impl Directory {
+    // Numbers from the biggest, ties broken by name
+    fn sort(&mut self, column: SortColumn, reverse: bool) {
+        let key = |directory: &Directory| match column {
+            SortColumn::Files => directory.stat.files,
+            SortColumn::Size => directory.stat.size,
+            SortColumn::Blank => directory.stat.blank,
+            SortColumn::Comment => directory.stat.comment,
+            SortColumn::Docs => directory.stat.docs,
+            SortColumn::Code => directory.stat.code,
+            _ => directory.stat.lines,
+        };
+        match column {
+            SortColumn::Name | SortColumn::Language => self.children.sort_by(|(a, _), (b, _)| a.cmp(b)),
+            _ => self.children.sort_by(|(a_name, a), (b_name, b)| key(b).cmp(&key(a)).then_with(|| a_name.cmp(b_name))),
+        }
+        if reverse {
+            self.children.reverse();
+        }
+        for (_, child) in self.children.iter_mut() {
+            child.sort(column, reverse);
+        }
+    }
+
+    fn collect<'a>(&'a self, directories: &mut Vec<&'a DirectoryStat>) {
+        directories.push(&self.stat);
+        for (_, child) in self.children.iter() {
+            child.collect(directories);
+        }
+    }
+
+    fn tree_rows(&self, name: String, prefix: &str, rows: &mut Vec<Vec<String>>) {
+        rows.push(vec![
+            name,
+            self.stat.files.to_string(),
+            self.stat.size.to_string(),
+            self.stat.blank.to_string(),
+            self.stat.comment.to_string(),
+            self.stat.docs.to_string(),
+            self.stat.code.to_string(),
+            self.stat.lines.to_string(),
+        ]);
+        let last = self.children.len().saturating_sub(1);
+        for (index, (name, child)) in self.children.iter().enumerate() {
+            let (branch, indent) = match index == last {
+                true => ("└── ", "    "),
+                false => ("├── ", "│   "),
+            };
+            child.tree_rows(format!("{}{}{}", prefix, branch, name), &format!("{}{}", prefix, indent), rows);
+        }
+    }
+}
+
+fn parent_components(path: &str) -> Vec<String> {
+    let mut components = Path::new(path)
+        .components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    components.pop(); // the file name
+    components
+}
+
+fn display_path(components: &[String]) -> String {
+    match components.is_empty() {
+        true => ".".to_string(),
+        false => components.iter().collect::<PathBuf>().to_string_lossy().into_owned(),
+    }
+}
+
+fn common_prefix(directories: &[Vec<String>]) -> Vec<String> {
+    let mut common = match directories.first() {
+        Some(directory) => directory.clone(),
+        None => return Vec::new(),
+    };
+    for directory in directories.iter().skip(1) {
+        let same = common.iter().zip(directory.iter()).take_while(|(a, b)| a == b).count();
+        common.truncate(same);
+    }
+    common
+}
@@ -0,0 +1,96 @@
+use regex::Regex;
+use once_cell::sync::Lazy;
+
+use crate::languages_mapping::{Embedding, Language, EXTENSIONS, LANGUAGES, MODELINE_NAMES};
+
+// <script>, <script lang="ts"> or <style lang="scss">, the content must start on the next line
+static HTML_OPENING_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)^\s*<(script|style)\b([^>]*)>\s*$").unwrap());
+static HTML_LANG: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(?i)\b(?:lang|type)\s*=\s*["']?(?:text/|application/)?([\w+#.-]+)"#).unwrap());
+// ```rust or ~~~ python, indented by at most 3 spaces
+static MARKDOWN_FENCE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^ {0,3}(`{3,}|~{3,})\s*([\w+#.-]*)").unwrap());
+
+enum Closing {
+    Tag(String),
+    Fence(String),
+}
+
+// Finds the regions of a file written in another language than the one of the
+// file: <script> and <style> in HTML, Vue and Svelte, fenced code blocks in
+// Markdown. The lines opening and closing a region belong to the file language
+pub struct EmbeddedRegions {
+    embedding: Embedding,
+    // language of the region we are in, None for a code block in an unknown language
+    region: Option<(Option<&'static Language>, Closing)>,
+}
+
+impl EmbeddedRegions {
+    pub fn new(embedding: Embedding) -> EmbeddedRegions {
+        EmbeddedRegions {
+            embedding,
+            region: None,
+        }
+    }
+
+    // Language of the line, None when it belongs to the language of the file
+    pub fn language_of(&mut self, line: &str) -> Option<&'static Language> {
+        match &self.region {
+            Some((language, closing)) => {
+                let closed = match closing {
+                    Closing::Tag(tag) => line.to_lowercase().contains(tag.as_str()),
+                    Closing::Fence(fence) => {
+                        let line = line.trim();
+                        line.starts_with(fence.as_str()) && line.chars().all(|c| c == fence.chars().next().unwrap())
+                    }
+                };
+                match closed {
+                    true => {
+                        self.region = None;
+                        None
+                    }
+                    false => *language,
+                }
+            }
+            None => {
+                self.region = match self.embedding {
+                    Embedding::Html => html_region(line),
+                    Embedding::Markdown => markdown_region(line),
+                };
+                None
+            }
+        }
+    }
+}
+
+fn html_region(line: &str) -> Option<(Option<&'static Language>, Closing)> {
+    let captures = HTML_OPENING_TAG.captures(line)?;
+    let tag = captures.get(1).unwrap().as_str().to_lowercase();
+    let lang = HTML_LANG.captures(captures.get(2).unwrap().as_str()).map(|lang| lang.get(1).unwrap().as_str());
+    let language = match (tag.as_str(), lang) {
+        // type="module", type="text/javascript"...
+        (_, Some(lang)) if language_by_name(lang).is_some() => language_by_name(lang),
+        ("script", Some(lang)) if lang.to_lowercase().contains("json") => LANGUAGES.get("JSON"),
+        ("script", _) => LANGUAGES.get("Javascript"),
+        _ => LANGUAGES.get("CSS"),
+    };
+    Some((language, Closing::Tag(format!("</{}", tag))))
+}
+
+fn markdown_region(line: &str) -> Option<(Option<&'static Language>, Closing)> {
+    let captures = MARKDOWN_FENCE.captures(line)?;
+    let fence = captures.get(1).unwrap().as_str();
+    // a backtick fence can't have backticks in its info string
+    if fence.starts_with('`') && line.trim_start().trim_start_matches('`').contains('`') {
+        return None;
+    }
+    let language = language_by_name(captures.get(2).unwrap().as_str());
+    Some((language, Closing::Fence(fence.to_string())))
+}
+
+// By name or alias (rust, c++, py) or by extension (rs, cpp)
+fn language_by_name(name: &str) -> Option<&'static Language> {
+    if name.is_empty() {
+        return None;
+    }
+    let name = MODELINE_NAMES.get(&name.to_lowercase()).or_else(|| EXTENSIONS.get(name))?;
+    LANGUAGES.get(*name)
+}
@@ -0,0 +1,85 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+// Number of bytes at the beginning of a file looked at to tell if it is binary
+pub const SNIFF_SIZE: usize = 8 * 1024;
+
+// Formats that may have no NUL byte in their first bytes: archives, compressed
+// files, images, documents and executables
+const MAGIC_NUMBERS: [&[u8]; 16] = [
+    b"\x7fELF", b"MZ\x90\x00", b"\xca\xfe\xba\xbe", b"\xcf\xfa\xed\xfe", b"\xce\xfa\xed\xfe",
+    b"PK\x03\x04", b"\x1f\x8b", b"BZh91AY&SY", b"\xfd7zXZ", b"7z\xbc\xaf\x27\x1c", b"Rar!\x1a\x07",
+    b"%PDF-", b"\x89PNG\r\n\x1a\n", b"GIF8", b"\xff\xd8\xff", b"SQLite format 3",
+];
+
+pub enum Decoded {
+    Text(String),
+    // NUL bytes in a file which is neither UTF-8 nor UTF-16
+    Binary,
+    // malformed for the encoding of its BOM, or with control characters once read as Latin-1
+    Undecodable,
+}
+
+// The encoding of a file and the length of its BOM, None for a binary file. Tried
+// in this order: BOM, UTF-8, UTF-16 without BOM (every other byte is NUL for the
+// ASCII characters) and Latin-1, read as windows-1252 like the browsers do
+fn guess_encoding(bytes: &[u8]) -> Option<(&'static Encoding, usize)> {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return Some((encoding, bom_length));
+    }
+    let nul_bytes = |parity: usize| bytes.iter().skip(parity).step_by(2).filter(|&&byte| byte == 0).count();
+    let (even, odd) = (nul_bytes(0), nul_bytes(1));
+    if even + odd == 0 {
+        // a character cut at the end of the head of a file is still utf-8
+        let utf8 = match std::str::from_utf8(bytes) {
+            Ok(_) => true,
+            Err(error) => error.error_len().is_none(),
+        };
+        return match utf8 {
+            true => Some((UTF_8, 0)),
+            false => Some((WINDOWS_1252, 0)),
+        };
+    }
+    let half = bytes.len() / 2;
+    if odd > half / 2 && even * 10 < odd {
+        return Some((UTF_16LE, 0));
+    }
+    if even > half / 2 && odd * 10 < even {
+        return Some((UTF_16BE, 0));
+    }
+    None
+}
+
+// Only looks at the first SNIFF_SIZE bytes: a known magic number, or NUL bytes
+// in a file which is not UTF-16
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let head = &bytes[..bytes.len().min(SNIFF_SIZE)];
+    MAGIC_NUMBERS.iter().any(|magic| head.starts_with(magic)) || guess_encoding(head).is_none()
+}
+
+pub fn decode(bytes: &[u8]) -> Decoded {
+    let (encoding, bom_length) = match guess_encoding(bytes) {
+        Some(guess) => guess,
+        None => return Decoded::Binary,
+    };
+    let text = match encoding.decode_without_bom_handling_and_without_replacement(&bytes[bom_length..]) {
+        Some(text) => text.into_owned(),
+        None => return Decoded::Undecodable,
+    };
+    // the bytes windows-1252 does not define end up as C1 control characters
+    let control = |c: char| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c' | '\x1b');
+    if encoding == WINDOWS_1252 && text.chars().any(control) {
+        return Decoded::Undecodable;
+    }
+    Decoded::Text(text)
+}
+
+// For the beginning and the end of a file, read to detect its language. They
+// are decoded with the encoding guessed from the head, replacing what is not
+// valid like a character cut in the middle
+pub fn decode_head_and_tail(head: &[u8], tail: &[u8]) -> (String, String) {
+    let (encoding, bom_length) = guess_encoding(head).unwrap_or((UTF_8, 0));
+    (
+        encoding.decode_without_bom_handling(&head[bom_length..]).0.into_owned(),
+        encoding.decode_without_bom_handling(tail).0.into_owned(),
+    )
+}
@@ -1,7 +1,7 @@
 use std::{
     fs,
+    io,
     path::Path,
-    process::exit,
     collections::HashSet,
     sync::Mutex,
     sync::atomic::{
@@ -10,60 +10,134 @@ use std::{
     },
 };
 
+use ignore::WalkBuilder;
 use rayon::prelude::*;
 
-use crate::file_supplier::ExtractInfo;
-use crate::search::Search;
+use crate::cli::{
+    FilesArgs, MetricArgs, SearchArgs, TodoArgs, OutputFormat,
+    EXIT_SUCCESS, EXIT_NOTHING_FOUND, EXIT_ERROR,
+};
+use crate::error::PathError;
+use crate::file_filter::FileFilter;
+use crate::file_handler::FileHandler;
+use crate::file_supplier::{ExtractInfo, Skipped};
+use crate::search::{Matcher, Search};
 
-pub fn get_stat(arg: Vec<String>) {
-    if arg.is_empty() {
-        println!("No file or directory specified");
-        exit(1);
+pub fn get_stat(args: &MetricArgs) -> u8 {
+    let mut errors = Vec::new();
+    let files = get_files(&args.files, &mut errors);
+    if files.is_empty() {
+        eprintln!("No file found");
+        report_errors(&errors);
+        return match args.files.strict && !errors.is_empty() {
+            true => EXIT_ERROR,
+            false => EXIT_NOTHING_FOUND,
+        };
     }
+    get_file_stat(files, args, errors)
+}
+
+pub fn search_for(args: &SearchArgs) -> u8 {
+    let mut patterns = args.regex.clone();
+    if let Some(path) = &args.patterns_file {
+        match fs::read_to_string(path) {
+            Ok(content) => patterns.extend(content.lines().filter(|line| !line.trim().is_empty()).map(str::to_owned)),
+            Err(error) => {
+                eprintln!("error: can't read the patterns of {}: {}", path, error);
+                return EXIT_ERROR;
+            }
+        }
+        if patterns.is_empty() {
+            eprintln!("error: no pattern in {}", path);
+            return EXIT_ERROR;
+        }
+    }
+    let matcher = match Matcher::from_args(args, &patterns) {
+        Ok(matcher) => matcher,
+        Err(error) => {
+            eprintln!("error: invalid regex: {}", error);
+            return EXIT_ERROR;
+        }
+    };
+    let pattern = match patterns.len() {
+        0 => args.pattern.clone().unwrap(),
+        1..=3 => patterns.join(", "),
+        _ => format!("{} patterns", patterns.len()),
+    };
+    let before = args.before_context.or(args.context).unwrap_or(0);
+    let after = args.after_context.or(args.context).unwrap_or(0);
+    search(&args.files, &pattern, Some(matcher), (before, after))
+}
 
-    let file = get_files(arg);
-    get_file_stat(file);
+pub fn search_todo(args: &TodoArgs) -> u8 {
+    search(&args.files, "TODO", None, (0, 0))
 }
 
-pub fn search_for(arg: Vec<String>) {
-    if arg.is_empty() {
-        println!("No argument given");
-        exit(1);
+// Without a matcher, search for the todo comments. context is the number of
+// lines shown before and after each match
+fn search(files_args: &FilesArgs, pattern: &str, matcher: Option<Matcher>, context: (usize, usize)) -> u8 {
+    let mut errors = Vec::new();
+    let files = get_files(files_args, &mut errors);
+    if files.is_empty() {
+        eprintln!("No file found");
+        report_errors(&errors);
+        return match files_args.strict && !errors.is_empty() {
+            true => EXIT_ERROR,
+            false => EXIT_NOTHING_FOUND,
+        };
     }
-     
-    let mut arg_copy = arg.clone();
 
-    let pattern = match arg[0].as_str() {
-        "-t" | "--todo" => "TODO",
-       _ => &arg[0],
-    };
     println!("Searching for {}\n", pattern);
-    arg_copy.remove(0);
-
-    let files = get_files(arg_copy);
-    let mut to_search= Search::new(files, pattern);
-    to_search.give_and_output_search();
+    let mut to_search = Search::new(files, pattern, matcher);
+    to_search.add_context(context.0, context.1);
+    to_search.give_search();
+    errors.extend(to_search.take_errors());
+    // reported before the interactive list, which may not return
+    report_errors(&errors);
+    if files_args.strict && !errors.is_empty() {
+        return EXIT_ERROR;
+    }
+    match to_search.output_search() {
+        true => EXIT_SUCCESS,
+        false => EXIT_NOTHING_FOUND,
+    }
 }
 
-fn get_files(args: Vec<String>) -> Vec<String> {
-    if args.is_empty() {
-        println!("No file specified");
-        exit(1);
+// The paths skipped because of an error, on stderr to keep them out of the output
+fn report_errors(errors: &[PathError]) {
+    if errors.is_empty() {
+        return;
+    }
+    let paths = match errors.len() {
+        1 => "path",
+        _ => "paths",
+    };
+    eprintln!("\n{} {} skipped because of errors:", errors.len(), paths);
+    for error in errors {
+        eprintln!("  {}", error);
     }
+}
+
+// The paths that can't be looked into are added to errors
+fn get_files(args: &FilesArgs, errors: &mut Vec<PathError>) -> Vec<FileHandler> {
+    let filter = FileFilter::new(args);
     let mut file = Vec::new();
-    for arg in args {
-        match Path::new(&arg).is_file() {
+    for arg in &args.paths {
+        match Path::new(arg).is_file() {
             true => {
-                file.push(arg);
+                let file_handler = FileHandler::new(arg);
+                if filter.is_match(arg, &file_handler) {
+                    file.push(file_handler);
+                }
             }
             false => {
-                match Path::new(&arg).is_dir() {
+                match Path::new(arg).is_dir() {
                     true => {
-                        let a = get_files_in_path(&arg.as_str());
-                        file.extend(a);
+                        let a = get_files_in_path(arg.as_str(), args.no_ignore, errors);
+                        file.extend(a.into_iter().map(|path| FileHandler::new(&path)).filter(|file| filter.is_match(arg, file)));
                     }
                     false => {
-                        println!("{} is not a file or directory", arg);
+                        errors.push(PathError::NotAFileOrDirectory(arg.to_owned()));
                     }
                 }
             }
@@ -71,30 +145,46 @@ fn get_files(args: Vec<String>) -> Vec<String> {
     }
 
     let file = remove_duplicate(file);
-
-    if file.is_empty() { // needed ?
-        println!("No file found");
-        exit(1);
+    if args.verbose {
+        print_languages(&file);
     }
     file
 }
 
-fn get_files_in_path(path: &str) -> Vec<String> {
-    let path = Path::new(path);
+fn print_languages(files: &[FileHandler]) {
+    for file in files {
+        match file.detect_language() {
+            Some((language, rule)) => eprintln!("{}: {} (by {})", file.get_path(), language.get_name(), rule),
+            None => eprintln!("{}: Unknown", file.get_path()),
+        }
+    }
+}
+
+// Hidden files and directories are always skipped. Unless no_ignore is set, the
+// .gitignore (nested ones and the global git excludes too), .ignore and .mopignore
+// files found along the way are respected
+fn get_files_in_path(path: &str, no_ignore: bool, errors: &mut Vec<PathError>) -> Vec<String> {
     let mut file_names = vec![];
 
-    for entry in fs::read_dir(path).unwrap() {
-        let entry = entry.unwrap();
-        let file_type = entry.file_type().unwrap();
-        let file_name = entry.file_name();
-        if file_type.is_file() {
-            if !file_name.to_str().unwrap().starts_with("."){
-                file_names.push(entry.path().into_os_string().into_string().unwrap());
+    let mut walker = WalkBuilder::new(path);
+    walker.standard_filters(!no_ignore).hidden(true);
+    if !no_ignore {
+        walker.add_custom_ignore_filename(".mopignore");
+    }
+    let walker = walker.build();
+
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                errors.push(PathError::Walk(error));
+                continue;
             }
-        } else if file_type.is_dir() {
-            let subdir_path = entry.path();
-            if !subdir_path.to_str().unwrap().split('/').last().unwrap().starts_with(".") {
-                file_names.append(&mut get_files_in_path(subdir_path.to_str().unwrap()));
+        };
+        if entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+            match entry.into_path().into_os_string().into_string() {
+                Ok(file_name) => file_names.push(file_name),
+                Err(file_name) => errors.push(PathError::NonUtf8Path(file_name.into())),
             }
         }
     }
@@ -102,53 +192,113 @@ fn get_files_in_path(path: &str) -> Vec<String> {
     file_names
 }
 
-fn remove_duplicate(file: Vec<String>) -> Vec<String> {
-    let file : Vec<String> = file.into_iter().collect::<HashSet<String>>().into_iter().collect();
-    file
+fn remove_duplicate(file: Vec<FileHandler>) -> Vec<FileHandler> {
+    let mut paths = HashSet::new();
+    file.into_iter().filter(|file| paths.insert(file.get_path().to_owned())).collect()
 }
 
-fn get_file_stat(file: Vec<String>) {
+fn get_file_stat(file: Vec<FileHandler>, args: &MetricArgs, mut errors: Vec<PathError>) -> u8 {
+    // the progress line is erased by the table, which a pipe or a file would keep
+    let show_progress = args.format == OutputFormat::Table && termion::is_tty(&io::stdout());
     let mut extract_info = ExtractInfo::new();
 
     let number_of_files = AtomicUsize::new(0);
     let number_of_files_ignore = AtomicUsize::new(0);
+    let number_of_files_undecodable = AtomicUsize::new(0);
     let total_size = AtomicUsize::new(0);
     let total_lines = AtomicUsize::new(0);
     let total_blank_lines = AtomicUsize::new(0);
     let total_comment_lines = AtomicUsize::new(0);
+    let total_doc_lines = AtomicUsize::new(0);
     let total_code_lines = AtomicUsize::new(0);
+    let total_mixed_lines = AtomicUsize::new(0);
     let file_stats_vec = Mutex::new(vec![]);
+    let file_errors = Mutex::new(vec![]);
 
-    file.par_iter().for_each(|arg| {
-        match extract_info.get_argument(&arg) {
-            Ok(file_stat) => {
+    // each file is dropped, with its content, once counted
+    file.into_par_iter().for_each(|file| {
+        match extract_info.get_argument(&file, args.mixed) {
+            Ok(file_stats) => {
                 number_of_files.fetch_add(1, Ordering::Relaxed);
-                print!("Number of files : {}\r",  number_of_files.load(Ordering::Relaxed));
-                total_size.fetch_add(file_stat.get_size(), Ordering::Relaxed);
-                total_lines.fetch_add(file_stat.get_lines(), Ordering::Relaxed);
-                total_blank_lines.fetch_add(file_stat.get_blank_lines(), Ordering::Relaxed);
-                total_comment_lines.fetch_add(file_stat.get_comment_lines(), Ordering::Relaxed);
-                total_code_lines.fetch_add(file_stat.get_code_lines(), Ordering::Relaxed);
+                if show_progress {
+                    print!("Number of files : {}\r",  number_of_files.load(Ordering::Relaxed));
+                }
+                // one stat per language, when other languages are embedded in the file
+                for file_stat in file_stats.iter() {
+                    total_size.fetch_add(file_stat.get_size(), Ordering::Relaxed);
+                    total_lines.fetch_add(file_stat.get_lines(), Ordering::Relaxed);
+                    total_blank_lines.fetch_add(file_stat.get_blank_lines(), Ordering::Relaxed);
+                    total_comment_lines.fetch_add(file_stat.get_comment_lines(), Ordering::Relaxed);
+                    total_doc_lines.fetch_add(file_stat.get_doc_lines(), Ordering::Relaxed);
+                    total_code_lines.fetch_add(file_stat.get_code_lines(), Ordering::Relaxed);
+                    total_mixed_lines.fetch_add(file_stat.get_mixed_lines(), Ordering::Relaxed);
+                }
                 let mut file_stats_vec = file_stats_vec.lock().unwrap();
-                file_stats_vec.push(file_stat);
+                file_stats_vec.extend(file_stats);
             }
-            Err(_) => {
+            Err(Skipped::Ignored) => {
                 number_of_files_ignore.fetch_add(1, Ordering::Relaxed);
             }
+            Err(Skipped::Undecodable) => {
+                number_of_files_undecodable.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(Skipped::Error(error)) => {
+                file_errors.lock().unwrap().push(error);
+            }
         }
     });
+    errors.extend(file_errors.into_inner().unwrap());
+    if args.files.strict && !errors.is_empty() {
+        if show_progress {
+            print!("\x1B[2K\r");
+        }
+        report_errors(&errors);
+        return EXIT_ERROR;
+    }
 
     extract_info.add_number_of_files(number_of_files.load(Ordering::Relaxed));
     extract_info.add_number_of_files_ignore(number_of_files_ignore.load(Ordering::Relaxed));
+    extract_info.add_number_of_files_undecodable(number_of_files_undecodable.load(Ordering::Relaxed));
     extract_info.add_total_size(total_size.load(Ordering::Relaxed));
     extract_info.add_tot_lines(total_lines.load(Ordering::Relaxed));
     extract_info.add_tot_blank_lines(total_blank_lines.load(Ordering::Relaxed));
     extract_info.add_tot_comment_lines(total_comment_lines.load(Ordering::Relaxed));
+    extract_info.add_tot_doc_lines(total_doc_lines.load(Ordering::Relaxed));
     extract_info.add_tot_code_lines(total_code_lines.load(Ordering::Relaxed));
-    for file_stat in file_stats_vec.lock().unwrap().iter() {
-        extract_info.add_stat_for_each_language(file_stat.to_owned());
+    extract_info.add_tot_mixed_lines(total_mixed_lines.load(Ordering::Relaxed));
+    for file_stat in file_stats_vec.into_inner().unwrap() {
+        extract_info.add_stat_for_each_language(file_stat);
+    }
+    if let Some(depth) = args.by_dir {
+        extract_info.group_by_directory(depth.unwrap_or(usize::MAX));
+        extract_info.sort_directories(args.sort, args.reverse);
+    }
+    extract_info.sort_stats_per_language(args.sort, args.reverse);
+    if args.by_file {
+        extract_info.sort_file_stats(args.sort, args.reverse, args.top);
+    }
+
+    match args.format {
+        OutputFormat::Table => {
+            if show_progress {
+                print!("\x1B[2K\r");
+            }
+            if args.by_file {
+                print!("{}", extract_info.to_file_table());
+            } else if let Some(directory_table) = extract_info.to_directory_table() {
+                print!("{}", directory_table);
+            } else {
+                println!("{}", extract_info);
+            }
+        }
+        OutputFormat::Json => println!("{}", extract_info.to_json(args.by_file)),
+        OutputFormat::Csv => print!("{}", extract_info.to_csv(args.by_file)),
+        OutputFormat::Markdown => print!("{}", extract_info.to_markdown(args.by_file)),
+    }
+    report_errors(&errors);
+
+    match number_of_files.load(Ordering::Relaxed) {
+        0 => EXIT_NOTHING_FOUND,
+        _ => EXIT_SUCCESS,
     }
-    
-    print!("\x1B[2K");
-    println!("{}", extract_info)
 }
@@ -0,0 +1,26 @@
+use std::{fmt, io, path::PathBuf};
+
+// A path that could not be looked into. The run goes on without it, and the
+// errors are reported together at the end (or make it fail with --strict)
+#[derive(Debug)]
+pub enum PathError {
+    // directory that could not be listed, broken symlink, loop...
+    Walk(ignore::Error),
+    // file that could not be opened or read
+    Read(String, io::Error),
+    NonUtf8Path(PathBuf),
+    NotAFileOrDirectory(String),
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::Walk(error) => write!(f, "{}", error),
+            PathError::Read(path, error) => write!(f, "{}: {}", path, error),
+            PathError::NonUtf8Path(path) => write!(f, "{}: the path is not valid UTF-8", path.display()),
+            PathError::NotAFileOrDirectory(path) => write!(f, "{}: not a file or directory", path),
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
@@ -0,0 +1,74 @@
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::cli::FilesArgs;
+use crate::file_handler::FileHandler;
+
+// The --include, --exclude and --lang options, shared by metric, search and todo
+pub struct FileFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    languages: Vec<String>,
+}
+
+impl FileFilter {
+    pub fn new(args: &FilesArgs) -> FileFilter {
+        FileFilter {
+            include: build_glob_set(&args.include),
+            exclude: build_glob_set(&args.exclude),
+            languages: args.lang.clone(),
+        }
+    }
+
+    // root is the file or directory given on the command line that led to file.
+    // The language detected for --lang is kept by file for the next passes
+    pub fn is_match(&self, root: &str, file: &FileHandler) -> bool {
+        let candidates = candidates(root, file.get_path());
+
+        if let Some(include) = &self.include {
+            if !candidates.iter().any(|candidate| include.is_match(candidate)) {
+                return false;
+            }
+        }
+        if let Some(exclude) = &self.exclude {
+            if candidates.iter().any(|candidate| exclude.is_match(candidate)) {
+                return false;
+            }
+        }
+        if !self.languages.is_empty() {
+            let language = match file.get_language() {
+                Some(language) => language.get_name(),
+                None => "Unknown",
+            };
+            return self.languages.iter().any(|l| l == language);
+        }
+        true
+    }
+}
+
+fn build_glob_set(globs: &[String]) -> Option<GlobSet> {
+    if globs.is_empty() {
+        return None;
+    }
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).unwrap()); // already validated by the cli
+    }
+    Some(builder.build().unwrap())
+}
+
+// A glob matches if it matches the path as written, the path relative to the
+// directory given on the command line or just the file name
+fn candidates<'a>(root: &str, path: &'a str) -> Vec<&'a str> {
+    let mut candidates = vec![path.trim_start_matches("./")];
+    if let Ok(relative) = Path::new(path).strip_prefix(root) {
+        if let Some(relative) = relative.to_str().filter(|r| !r.is_empty()) {
+            candidates.push(relative);
+        }
+    }
+    if let Some(file_name) = Path::new(path).file_name().and_then(|name| name.to_str()) {
+        candidates.push(file_name);
+    }
+    candidates
+}
@@ -1,230 +1,395 @@
 use std::{
+    fmt,
     fs,
     io::Read,
+    path::Path,
 };
 
 use regex::Regex;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::Serialize;
 
+use crate::cli::MixedLines;
 use crate::languages_mapping::{
     Language,
-    LANGUAGES, EXTENSIONS,
+    LANGUAGES, EXTENSIONS, FILENAMES, HEURISTICS, INTERPRETERS, MODELINE_NAMES,
 };
+use crate::embedded::EmbeddedRegions;
+use crate::encoding::{self, Decoded};
+use crate::error::PathError;
+use crate::line_classifier::{LineClassifier, LineKind};
+use crate::search::{Matcher, SearchLine};
+
+
+static IS_BLANK: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*$").unwrap());
+// vim: set ft=python: / vi: filetype=python / ex: syntax=python
+static VIM_MODELINE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?:^|\s)(?:vi|vim|ex):.*?\b(?:ft|filetype|syntax)=([\w+#.-]+)").unwrap());
+// -*- mode: python -*- / -*- python -*-
+static EMACS_MODELINE: Lazy<Regex> = Lazy::new(|| Regex::new(r"-\*-\s*(.*?)\s*-\*-").unwrap());
+static EMACS_MODE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)(?:^|;)\s*mode\s*:\s*([\w+#.-]+)").unwrap());
+
+// Only the beginning and the end of a file are read to find its shebang, modelines
+// and to run the heuristics, which need a bigger part of the file
+const HEAD_SIZE: u64 = 64 * 1024;
+const TAIL_SIZE: u64 = 4096;
+// vim looks for modelines in the first and last 5 lines
+const MODELINE_LINES: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DetectionRule {
+    Filename,
+    Extension,
+    Heuristic,
+    Shebang,
+    Modeline,
+}
 
-
-const IS_BLANK: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*$").unwrap());
-static EMPTY_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"").unwrap());
+impl fmt::Display for DetectionRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rule = match self {
+            DetectionRule::Filename => "file name",
+            DetectionRule::Extension => "extension",
+            DetectionRule::Heuristic => "content heuristic",
+            DetectionRule::Shebang => "shebang",
+            DetectionRule::Modeline => "modeline",
+        };
+        write!(f, "{}", rule)
+    }
+}
 
 pub struct FileHandler {
     path: String,
+    // the whole file, read at most once and shared by the language detection,
+    // the stats and the search
+    bytes: OnceCell<Vec<u8>>,
+    // detected at most once, by the --lang filter, --verbose, the stats or the search
+    language: OnceCell<Option<(&'static Language, DetectionRule)>>,
 }
 
 impl FileHandler {
     pub fn new(path: &str) -> FileHandler {
         FileHandler {
             path: path.to_string(),
+            bytes: OnceCell::new(),
+            language: OnceCell::new(),
         }
     }
 
-    pub fn is_file(&self) -> bool {
-        fs::metadata(&self.path).unwrap().is_file()
-    }
-
-    fn open_file(&self) -> fs::File {
-        fs::File::open(&self.path).unwrap()
+    pub fn get_path(&self) -> &str {
+        &self.path
     }
 
-    pub fn is_binary(&self) -> bool {
-        let mut file = self.open_file();
-        match file.read_to_string(&mut String::new()) {
-            Ok(_) => false,
-            Err(_) => true,
-        }
+    pub fn is_file(&self) -> bool {
+        fs::metadata(&self.path).is_ok_and(|metadata| metadata.is_file())
     }
 
-    fn is_line_blank(&self, line: &str) -> bool {
-        IS_BLANK.is_match(line)
+    fn open_file(&self) -> Result<fs::File, PathError> {
+        fs::File::open(&self.path).map_err(|error| PathError::Read(self.path.clone(), error))
     }
 
-    fn is_line_single_comment(&self, line: &str, single_line_comment: &Regex) -> bool {
-        single_line_comment.is_match(line)
+    fn read_bytes(&self) -> Result<&[u8], PathError> {
+        let bytes = self.bytes.get_or_try_init(|| {
+            let mut bytes = Vec::new();
+            self.open_file()?.read_to_end(&mut bytes).map_err(|error| PathError::Read(self.path.clone(), error))?;
+            Ok(bytes)
+        })?;
+        Ok(bytes)
     }
 
-    fn is_line_block_comment_start(&self, line: &str, start_comment_on_block_line: &Regex) -> bool {
-        start_comment_on_block_line.is_match(line)
+    // UTF-8, UTF-16 or Latin-1, transcoded to UTF-8
+    pub fn read_content(&self) -> Result<Decoded, PathError> {
+        Ok(encoding::decode(self.read_bytes()?))
     }
 
-    fn is_line_block_comment_end(&self, line: &str, end_comment_on_block_line: &Regex) -> bool {
-        end_comment_on_block_line.is_match(line)
+    // Sniffed from the beginning of the file, which is then read whole in the
+    // same buffer when it is text: the file is opened once, and a binary file is
+    // never read whole
+    pub fn is_binary(&self) -> Result<bool, PathError> {
+        if let Some(bytes) = self.bytes.get() {
+            return Ok(encoding::is_binary(bytes));
+        }
+        let read_error = |error| PathError::Read(self.path.clone(), error);
+        let mut file = self.open_file()?;
+        let mut bytes = Vec::new();
+        file.by_ref().take(encoding::SNIFF_SIZE as u64).read_to_end(&mut bytes).map_err(read_error)?;
+        if encoding::is_binary(&bytes) {
+            return Ok(true);
+        }
+        file.read_to_end(&mut bytes).map_err(read_error)?;
+        let _ = self.bytes.set(bytes);
+        Ok(false)
     }
 
-    fn read_file(&self) -> String {
-        let mut file = self.open_file();
-        let mut content = String::new();
-        file.read_to_string(&mut content).unwrap();
-        content
+    fn is_line_blank(&self, line: &str) -> bool {
+        IS_BLANK.is_match(line)
     }
 
-    fn is_file_unknow<'a>(&self, file_stat: &mut FileStats<'a>) -> FileStats<'a> {
+    fn is_file_unknow<'a>(&self, file: &str, file_stat: &mut FileStats<'a>) -> FileStats<'a> {
         //println!("File {} is not supported", self.path); // I don't know if this is useful
-        let file = self.read_file();
         file_stat.add_size(file.len());
-        let mut lines = file.lines();
-
         let mut blank_lines = 0;
         let mut code_lines = 0;
         let mut total_lines = 0;
 
-        while let Some(line) = lines.next() {
+        for line in file.lines() {
             if self.is_line_blank(line) {
                 blank_lines += 1;
             } else {
                 code_lines += 1;
             } 
             total_lines += 1;
-        };
+        }
         file_stat.add_blank_lines_tot(blank_lines);
         file_stat.add_code_lines_tot(code_lines);
         file_stat.add_lines_tot(total_lines);
         file_stat.to_owned()
     }
 
-    fn is_file_known<'a>(&self, language: Language , file_stat: &mut FileStats<'a>) -> FileStats<'a> {
-        let file = self.read_file();
-        file_stat.add_size(file.len());
-        let mut lines = file.lines();
-
-        let mut blank_lines = 0;
-        let mut comment_lines = 0;
-        let mut code_lines = 0;
-        let mut total_lines = 0;
-        let mut is_in_block_comment = false;
-
-        let mut block_line_comment_begin_exist = false;
-        let mut block_line_comment_end_exist = false;
-
-        let regex_single_line_comment = language.get_single_line_comment();
-        let regex_begin_comment_on_block_line = match language.get_block_line_comment_begin() {
-            Some(regex) => {
-                block_line_comment_begin_exist = true;
-                regex
-            },
-            None => &EMPTY_REGEX,
-        };
-        let regex_end_comment_on_block_line = match language.get_block_line_comment_end() {
-            Some(regex) => {
-                block_line_comment_end_exist = true;
-                regex
-            },
-            None => &EMPTY_REGEX,
-        };
-
-        while let Some(line) = lines.next() {
-            if !is_in_block_comment {
-                if self.is_line_blank(&line) {
-                    blank_lines += 1;
-                } else if self.is_line_single_comment(&line, &regex_single_line_comment) {
-                    comment_lines += 1;
-                } else if block_line_comment_begin_exist && self.is_line_block_comment_start(&line, &regex_begin_comment_on_block_line) {
-                    comment_lines += 1;
-                    is_in_block_comment = true;
-                } else {
-                    code_lines += 1;
+    // The file language first, followed by the languages embedded in it if any
+    fn is_file_known<'a>(&self, file: &str, language: &'a Language, mixed: MixedLines, mut file_stat: FileStats<'a>) -> Vec<FileStats<'a>> {
+        let mut classifier = LineClassifier::new(language);
+        let mut regions = language.get_embedding().map(EmbeddedRegions::new);
+        let mut embedded: Vec<(LineClassifier, FileStats<'a>)> = Vec::new();
+
+        for raw_line in file.split_inclusive('\n') {
+            let line = raw_line.trim_end_matches('\n').trim_end_matches('\r');
+            match regions.as_mut().and_then(|regions| regions.language_of(line)) {
+                Some(region) => {
+                    let index = match embedded.iter().position(|(_, stat)| stat.get_language() == region.get_name()) {
+                        Some(index) => index,
+                        None => {
+                            let mut stat = FileStats::new(&self.path);
+                            stat.add_language(region.get_name());
+                            stat.add_embedded_in(language.get_name());
+                            embedded.push((LineClassifier::new(region), stat));
+                            embedded.len() - 1
+                        }
+                    };
+                    let (classifier, stat) = &mut embedded[index];
+                    stat.add_size(raw_line.len());
+                    stat.add_line(classifier.classify(line), mixed);
                 }
-            } else {
-                if block_line_comment_end_exist && self.is_line_block_comment_end(&line, &regex_end_comment_on_block_line) {
-                    is_in_block_comment = false;
+                None => {
+                    file_stat.add_size(raw_line.len());
+                    file_stat.add_line(classifier.classify(line), mixed);
                 }
-                comment_lines += 1;
             }
-            total_lines += 1;
         }
 
-        file_stat.add_blank_lines_tot(blank_lines);
-        file_stat.add_comment_lines_tot(comment_lines);
-        file_stat.add_code_lines_tot(code_lines);
-        file_stat.add_lines_tot(total_lines);
+        let mut file_stats = vec![file_stat];
+        file_stats.extend(embedded.into_iter().map(|(_, stat)| stat));
+        file_stats
+    }
 
-        file_stat.to_owned()
+    // content is the decoded file, mixed is how the lines with both code and a
+    // comment are counted
+    pub fn get_file_stat<'a>(&self, content: &str, mixed: MixedLines) -> Vec<FileStats<'a>> {
+        let mut file_stat = FileStats::new(&self.path);
+        match self.get_language() {
+            Some(l) => {
+                file_stat.add_language(l.get_name());
+                self.is_file_known(content, l, mixed, file_stat)
+            }
+            None => vec![self.is_file_unknow(content, &mut file_stat)],
+        }
+    }
+
+    pub fn get_language(&self) -> Option<&'static Language> {
+        self.detect_language().map(|(language, _)| language)
     }
 
-    pub fn get_file_stat<'a>(&self) -> FileStats<'a> {
-        let mut file_stat = FileStats::new();
-        let file_stat = match self.get_language(&mut file_stat) {
-            Some(l) => self.is_file_known(l, &mut file_stat),
-            None => self.is_file_unknow(&mut file_stat),
+    // Tried in this order: exact file name, extension (with the heuristics of
+    // HEURISTICS when it is shared by several languages), interpreter of the
+    // shebang line and vim or emacs modeline. The file is only read when needed
+    pub fn detect_language(&self) -> Option<(&'static Language, DetectionRule)> {
+        *self.language.get_or_init(|| self.find_language())
+    }
+
+    fn find_language(&self) -> Option<(&'static Language, DetectionRule)> {
+        let path = Path::new(&self.path);
+        let file_name = path.file_name().and_then(|name| name.to_str());
+        let extension = path.extension().and_then(|extension| extension.to_str());
+
+        if let Some(language) = file_name.and_then(|name| FILENAMES.get(name)) {
+            return LANGUAGES.get(*language).map(|l| (l, DetectionRule::Filename));
+        }
+        let by_extension = extension.and_then(|extension| EXTENSIONS.get(extension)).and_then(|language| LANGUAGES.get(*language));
+        // the languages of the user are not known to the heuristics, and win over them
+        if let Some(language) = by_extension.filter(|language| language.is_user_defined()) {
+            return Some((language, DetectionRule::Extension));
+        }
+        let mut content = None;
+        if let Some(heuristics) = extension.and_then(|extension| HEURISTICS.get(extension)) {
+            content = self.read_head_and_tail();
+            let head = content.as_ref().map(|(head, _)| head.as_str()).unwrap_or_default();
+            let language = heuristics.iter()
+                .find(|heuristic| heuristic.is_match(head))
+                .and_then(|heuristic| LANGUAGES.get(heuristic.get_language()));
+            if let Some(language) = language {
+                return Some((language, DetectionRule::Heuristic));
+            }
+        }
+        if let Some(language) = by_extension {
+            return Some((language, DetectionRule::Extension));
+        }
+
+        let (head, tail) = match content {
+            Some(content) => content,
+            None => self.read_head_and_tail()?,
         };
-        file_stat
-    }
-
-    // TODO remove the FileStats parameter to use it in search and metric
-    fn get_language(&self, file_stat: &mut FileStats) -> Option<Language> {
-        let extension = self.path.split('.').last();
-        match extension {
-            Some(ext) => {
-                for (language, exts) in EXTENSIONS.iter() {
-                    if exts.contains(ext) {
-                        file_stat.add_language(language);
-                        return LANGUAGES.get(language).cloned();
-                    }
-                }
-                None
-            },
-            _ => None,
+        if let Some(language) = language_from_shebang(&head) {
+            return LANGUAGES.get(language).map(|l| (l, DetectionRule::Shebang));
         }
+        if let Some(language) = language_from_modeline(&head, &tail) {
+            return LANGUAGES.get(language).map(|l| (l, DetectionRule::Modeline));
+        }
+        None
     }
 
-    pub fn get_language_for_search(&self) -> Option<Language> {
-        let extension = self.path.split('.').last();
-        match extension {
-            Some(ext) => {
-                for (language, exts) in EXTENSIONS.iter() {
-                    if exts.contains(ext) {
-                        return LANGUAGES.get(language).cloned();
-                    }
-                }
-                None
-            },
-            _ => None,
+    // The tail is empty when the whole file fits in the head. None for a binary
+    // file, a text file being read whole once, for the detection and the counting
+    fn read_head_and_tail(&self) -> Option<(String, String)> {
+        if self.is_binary().ok()? {
+            return None;
         }
+        let bytes = self.bytes.get()?;
+        let head = &bytes[..bytes.len().min(HEAD_SIZE as usize)];
+        let tail = match bytes.len() as u64 > HEAD_SIZE {
+            true => &bytes[(bytes.len() - TAIL_SIZE as usize).max(HEAD_SIZE as usize)..],
+            false => &[],
+        };
+        Some(encoding::decode_head_and_tail(head, tail))
     }
 
-    pub fn search_pattern(&self, pattern: &str) -> Vec<(u32,String)> {
-        let file = self.read_file();
-        let mut lines = file.lines();
-        
+    // content is the decoded file
+    pub fn search_pattern(&self, content: &str, matcher: &Matcher) -> Vec<SearchLine> {
+        if matcher.is_multiline() {
+            return self.search_multiline(content, matcher);
+        }
         let mut result = Vec::new();
-        let mut file_number: u32 = 1;
-        while let Some(line) = lines.next() {
-            if line.contains(&pattern) || line.contains(&pattern.to_lowercase()) {
-                result.push((file_number, line.trim().to_owned()));
+        for (index, line) in content.lines().enumerate() {
+            let matches = matcher.find(line);
+            if let Some(first) = matches.first() {
+                result.push(SearchLine::new(index as u32 + 1, line, first.start, &matches));
             }
-            file_number += 1;
         }
         result
     }
 
+    // The matches starting on the same line make one result, which ends on the
+    // last line of the longest one
+    fn search_multiline(&self, content: &str, matcher: &Matcher) -> Vec<SearchLine> {
+        let line_starts = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(index, _)| index + 1))
+            .collect::<Vec<_>>();
+        let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+
+        let mut result: Vec<SearchLine> = Vec::new();
+        let mut matches = matcher.find_all(content).into_iter().peekable();
+        while let Some(first) = matches.next() {
+            let index = line_of(first.start);
+            let start = line_starts[index];
+            let line = content[start..].split('\n').next().unwrap().trim_end_matches('\r');
+            let mut same_line = vec![first];
+            while let Some(found) = matches.next_if(|found| line_of(found.start) == index) {
+                same_line.push(found);
+            }
+            let end_line = same_line.iter().map(|found| line_of(found.end.saturating_sub(1).max(found.start))).max().unwrap();
+            let relative = same_line.iter().map(|found| found.start - start..found.end - start).collect::<Vec<_>>();
+            let mut search_line = SearchLine::new(index as u32 + 1, line, relative[0].start, &relative);
+            search_line.add_end_line_number(end_line as u32 + 1);
+            result.push(search_line);
+        }
+        result
+    }
+}
+
+// #!/usr/bin/python3, #!/usr/bin/env python3 or #!/usr/bin/env -S python3 -u
+fn language_from_shebang(head: &str) -> Option<&'static str> {
+    let line = head.lines().next()?.strip_prefix("#!")?;
+    let mut words = line.split_whitespace();
+    let mut interpreter = Path::new(words.next()?).file_name()?.to_str()?;
+    if interpreter == "env" {
+        interpreter = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+    }
+
+    INTERPRETERS.get(interpreter)
+        .or_else(|| INTERPRETERS.get(interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')))
+        .copied()
 }
 
-#[derive(Clone)]
+// The emacs modeline must be on the first line, or on the second one after a shebang
+fn language_from_modeline(head: &str, tail: &str) -> Option<&'static str> {
+    let first_lines = head.lines().take(MODELINE_LINES).collect::<Vec<_>>();
+    let mut last_lines = match tail.is_empty() {
+        true => head.lines().rev().take(MODELINE_LINES).collect::<Vec<_>>(),
+        false => tail.lines().rev().take(MODELINE_LINES).collect::<Vec<_>>(),
+    };
+    last_lines.reverse();
+
+    let emacs_lines = match first_lines.first() {
+        Some(line) if line.starts_with("#!") => first_lines.iter().take(2),
+        _ => first_lines.iter().take(1),
+    };
+    for line in emacs_lines {
+        if let Some(captures) = EMACS_MODELINE.captures(line) {
+            let content = captures.get(1).unwrap().as_str();
+            let mode = match EMACS_MODE.captures(content) {
+                Some(mode) => mode.get(1).unwrap().as_str(),
+                None if !content.contains(':') => content,
+                None => continue,
+            };
+            if let Some(language) = MODELINE_NAMES.get(&mode.to_lowercase()) {
+                return Some(language);
+            }
+        }
+    }
+
+    for line in first_lines.iter().chain(last_lines.iter()) {
+        if let Some(captures) = VIM_MODELINE.captures(line) {
+            if let Some(language) = MODELINE_NAMES.get(&captures.get(1).unwrap().as_str().to_lowercase()) {
+                return Some(language);
+            }
+        }
+    }
+    None
+}
+
+#[derive(Clone, Serialize)]
 pub struct FileStats<'a> {
+    path: String,
     language: &'a str,
+    // language of the file, for the code of another language in it like a <script> in HTML
+    #[serde(skip_serializing_if = "Option::is_none")]
+    embedded_in: Option<&'a str>,
     size: usize,
-    lines : usize,
+    #[serde(rename = "blank")]
     blank_lines: usize,
+    #[serde(rename = "comment")]
     comment_lines: usize,
+    #[serde(rename = "docs")]
+    doc_lines: usize,
+    #[serde(rename = "code")]
     code_lines: usize,
+    // lines with both code and a comment, already counted in code_lines and/or comment_lines
+    #[serde(rename = "mixed")]
+    mixed_lines: usize,
+    lines : usize,
 }
 
 impl<'a> FileStats<'a> {
-    pub fn new() -> FileStats<'a> {
+    pub fn new(path: &str) -> FileStats<'a> {
         FileStats {
+            path: path.to_string(),
             language: "Unknown",
+            embedded_in: None,
             size: 0,
             lines: 0,
             blank_lines: 0,
             comment_lines: 0,
+            doc_lines: 0,
             code_lines: 0,
+            mixed_lines: 0,
         }
     }
 
@@ -232,6 +397,10 @@ impl<'a> FileStats<'a> {
         self.language = language;
     }
     
+    pub fn add_embedded_in(&mut self, language: &'a str) {
+        self.embedded_in = Some(language);
+    }
+
     pub fn add_size(&mut self, size: usize) {
         self.size += size;
     }
@@ -248,14 +417,47 @@ impl<'a> FileStats<'a> {
         self.comment_lines += comment_lines;
     }
 
+    pub fn add_doc_lines_tot(&mut self, doc_lines: usize) {
+        self.doc_lines += doc_lines;
+    }
+
     pub fn add_code_lines_tot(&mut self, code_lines: usize) {
         self.code_lines += code_lines;
     }
 
+    pub fn add_line(&mut self, kind: LineKind, mixed: MixedLines) {
+        self.lines += 1;
+        match kind {
+            LineKind::Blank => self.blank_lines += 1,
+            LineKind::Comment => self.comment_lines += 1,
+            LineKind::Doc => self.doc_lines += 1,
+            LineKind::Code => self.code_lines += 1,
+            LineKind::Mixed => {
+                self.mixed_lines += 1;
+                match mixed {
+                    MixedLines::Code => self.code_lines += 1,
+                    MixedLines::Comment => self.comment_lines += 1,
+                    MixedLines::Both => {
+                        self.code_lines += 1;
+                        self.comment_lines += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
     pub fn get_language(&self) -> &'a str {
         self.language
     }
 
+    pub fn get_embedded_in(&self) -> Option<&'a str> {
+        self.embedded_in
+    }
+
     pub fn get_size(&self) -> usize {
         self.size
     }
@@ -272,7 +474,85 @@ impl<'a> FileStats<'a> {
         self.comment_lines
     }
 
+    pub fn get_doc_lines(&self) -> usize {
+        self.doc_lines
+    }
+
     pub fn get_code_lines(&self) -> usize {
         self.code_lines
     }
+
+    pub fn get_mixed_lines(&self) -> usize {
+        self.mixed_lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search_multiline(content: &str, pattern: &str) -> Vec<(u32, u32, usize, String)> {
+        let matcher = Matcher::new(Regex::new(&format!("(?m){}", pattern)).unwrap());
+        FileHandler::new("main.rs").search_multiline(content, &matcher).into_iter()
+            .map(|found| (found.line_number, found.end_line_number, found.column, found.text))
+            .collect()
+    }
+
+    #[test]
+    fn multiline_matches_start_on_their_first_line() {
+        let content = "fn main() {\n    unsafe {\n        transmute(x)\n    }\n}\n";
+        assert_eq!(search_multiline(content, r"unsafe \{\s*transmute"), [(2, 3, 5, "unsafe {".to_string())]);
+        // the matches starting on the same line are shown together, up to the last line of the longest
+        assert_eq!(search_multiline("foo foo\nbar baz\n", r"o\s+\w"), [(1, 2, 3, "foo foo".to_string())]);
+        assert!(search_multiline(content, r"unsafe \{\s*\}").is_empty());
+    }
+
+    #[test]
+    fn multiline_matches_in_crlf_files() {
+        let content = "let foo = 1;\r\nfoo\r\n";
+        // the match starts on the \r trimmed from the first line
+        assert_eq!(search_multiline(content, r"\r\nfoo"), [(1, 2, 13, "let foo = 1;".to_string())]);
+        assert_eq!(search_multiline(content, r"\nfoo"), [(1, 2, 13, "let foo = 1;".to_string())]);
+        assert_eq!(search_multiline(content, r"1;\s+foo"), [(1, 2, 11, "let foo = 1;".to_string())]);
+    }
+
+    #[test]
+    fn languages_from_the_shebang() {
+        assert_eq!(language_from_shebang("#!/bin/sh\necho"), Some("Shell"));
+        assert_eq!(language_from_shebang("#!/usr/bin/env -S PYTHONPATH=. python3 -u\n"), Some("Python"));
+        // the version is not part of the name of the interpreter
+        assert_eq!(language_from_shebang("#!/usr/local/bin/ruby2.7\n"), Some("Ruby"));
+        assert_eq!(language_from_shebang("#!/usr/bin/unknown\n"), None);
+        assert_eq!(language_from_shebang("\n#!/bin/sh\n"), None);
+    }
+
+    #[test]
+    fn languages_from_the_modelines() {
+        assert_eq!(language_from_modeline("# -*- mode: ruby; coding: utf-8 -*-\nputs 1\n", ""), Some("Ruby"));
+        assert_eq!(language_from_modeline("#!/bin/tool\n# -*- python -*-\n", ""), Some("Python"));
+        // emacs only looks at the first line
+        assert_eq!(language_from_modeline("\n# -*- mode: ruby -*-\n", ""), None);
+        assert_eq!(language_from_modeline("x = 1\n# vim: set ft=python:\n", ""), Some("Python"));
+        assert_eq!(language_from_modeline("x = 1\n", "\n# vim: filetype=ruby\n"), Some("Ruby"));
+    }
+
+    #[test]
+    fn languages_from_the_file_names() {
+        let detect = |path: &str| FileHandler::new(path).detect_language().map(|(language, rule)| (language.get_name(), rule));
+        assert_eq!(detect("project/Makefile"), Some(("Makefile", DetectionRule::Filename)));
+        assert_eq!(detect("CMakeLists.txt"), Some(("CMake", DetectionRule::Filename)));
+        assert_eq!(detect("src/main.rs"), Some(("Rust", DetectionRule::Extension)));
+    }
+
+    #[test]
+    fn files_are_read_once_for_the_detection_and_the_counting() {
+        let path = std::env::temp_dir().join(format!("mop-read-once-{}", std::process::id()));
+        fs::write(&path, "#!/usr/bin/env python3\nprint(1)\n").unwrap();
+        let file = FileHandler::new(path.to_str().unwrap());
+        assert_eq!(file.detect_language().map(|(language, rule)| (language.get_name(), rule)), Some(("Python", DetectionRule::Shebang)));
+        // the content read for the shebang is the one sniffed and counted
+        fs::remove_file(&path).unwrap();
+        assert!(!file.is_binary().unwrap());
+        assert_eq!(file.bytes.get().map(Vec::as_slice), Some(&b"#!/usr/bin/env python3\nprint(1)\n"[..]));
+    }
 }
@@ -1,20 +1,54 @@
 use std::fmt;
 
+use serde::Serialize;
+
 use crate::{
+    cli::{MixedLines, SortColumn},
+    directory_tree::{DirectoryStat, DirectoryTree},
     languages_mapping::EXTENSIONS_TO_IGNORE,
+    encoding::Decoded,
+    error::PathError,
     file_handler::{FileHandler, FileStats},
 };
 
+// Why a file is left out of the counts
+pub enum Skipped {
+    // binary, or with an extension of EXTENSIONS_TO_IGNORE
+    Ignored,
+    // text in an encoding we can't read, or malformed for its encoding
+    Undecodable,
+    Error(PathError),
+}
+
+impl From<PathError> for Skipped {
+    fn from(error: PathError) -> Self {
+        Skipped::Error(error)
+    }
+}
+
 pub struct ExtractInfo {
     number_of_files: usize,
     number_of_files_ignore: usize,
+    number_of_files_undecodable: usize,
     number_of_directories: usize,
     total_size: usize,
     tot_lines : usize,
     tot_blank_lines: usize,
     tot_comment_lines: usize,
+    tot_doc_lines: usize,
     tot_code_lines: usize,
+    tot_mixed_lines: usize,
     stats_per_language: Vec<StatPerLanguage>,
+    file_stats: Vec<FileStats<'static>>,
+    // some files are not listed because of --top
+    file_stats_cut: bool,
+    directory_tree: Option<DirectoryTree>,
+}
+
+impl Default for ExtractInfo {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ExtractInfo {
@@ -22,13 +56,19 @@ impl ExtractInfo {
         ExtractInfo {
             number_of_files: 0,
             number_of_files_ignore: 0,
+            number_of_files_undecodable: 0,
             number_of_directories: 0,
             total_size: 0,
             tot_lines : 0,
             tot_blank_lines: 0,
             tot_comment_lines: 0,
+            tot_doc_lines: 0,
             tot_code_lines: 0,
+            tot_mixed_lines: 0,
             stats_per_language: Vec::new(),
+            file_stats: Vec::new(),
+            file_stats_cut: false,
+            directory_tree: None,
         }
     }
 
@@ -40,6 +80,10 @@ impl ExtractInfo {
         self.number_of_files_ignore += number_of_files_ignore;
     }
 
+    pub fn add_number_of_files_undecodable(&mut self, number_of_files_undecodable: usize) {
+        self.number_of_files_undecodable += number_of_files_undecodable;
+    }
+
     pub fn add_number_of_directories(&mut self) {
         self.number_of_directories += 1;
     }
@@ -60,60 +104,350 @@ impl ExtractInfo {
         self.tot_comment_lines += lines;
     }
 
+    pub fn add_tot_doc_lines(&mut self, lines: usize) {
+        self.tot_doc_lines += lines;
+    }
+
     pub fn add_tot_code_lines(&mut self, lines: usize) {
         self.tot_code_lines += lines;
     }
 
-    pub fn add_stat_for_each_language(&mut self, file_stat: FileStats) {
+    pub fn add_tot_mixed_lines(&mut self, lines: usize) {
+        self.tot_mixed_lines += lines;
+    }
+
+    // The code embedded in a file of another language adds to the lines of its
+    // language, but not to its number of files
+    pub fn add_stat_for_each_language(&mut self, file_stat: FileStats<'static>) {
         let mut found = false;
+        let is_file = file_stat.get_embedded_in().is_none();
         for stat in self.stats_per_language.iter_mut() {
             if stat.language == file_stat.get_language() {
-                stat.add_number_of_files();
+                if is_file {
+                    stat.add_number_of_files();
+                }
                 stat.add_total_size(file_stat.get_size());
                 stat.add_tot_lines(file_stat.get_lines());
                 stat.add_tot_blank_lines(file_stat.get_blank_lines());
                 stat.add_tot_comment_lines(file_stat.get_comment_lines());
+                stat.add_tot_doc_lines(file_stat.get_doc_lines());
                 stat.add_tot_code_lines(file_stat.get_code_lines());
+                stat.add_tot_mixed_lines(file_stat.get_mixed_lines());
                 found = true;
                 break;
             }
         }
         if !found {
-            self.stats_per_language.push(StatPerLanguage::new(
+            let mut stat = StatPerLanguage::new(
           file_stat.get_language().to_string(),
                     file_stat.get_size(),
                     file_stat.get_lines(),
                     file_stat.get_blank_lines(),
                     file_stat.get_comment_lines(),
+                    file_stat.get_doc_lines(),
          file_stat.get_code_lines(),
-            ));
+            );
+            if !is_file {
+                stat.number_of_files = 0;
+            }
+            stat.add_tot_mixed_lines(file_stat.get_mixed_lines());
+            self.stats_per_language.push(stat);
+        }
+        self.file_stats.push(file_stat);
+    }
+
+    // See the "JSON output" section of the README for the schema, bump
+    // JSON_SCHEMA_VERSION on any change that is not adding a field
+    pub fn to_json(&self, with_files: bool) -> String {
+        let output = JsonOutput {
+            version: JSON_SCHEMA_VERSION,
+            totals: JsonTotals {
+                files: self.number_of_files,
+                ignored_files: self.number_of_files_ignore,
+                undecodable_files: self.number_of_files_undecodable,
+                size: self.total_size,
+                blank: self.tot_blank_lines,
+                comment: self.tot_comment_lines,
+                docs: self.tot_doc_lines,
+                code: self.tot_code_lines,
+                mixed: self.tot_mixed_lines,
+                lines: self.tot_lines,
+            },
+            languages: &self.stats_per_language,
+            files: match with_files {
+                true => Some(&self.file_stats),
+                false => None,
+            },
+            directories: self.directory_tree.as_ref().map(|tree| tree.directories()),
+        };
+        serde_json::to_string_pretty(&output).unwrap()
+    }
+
+    pub fn to_csv(&self, by_file: bool) -> String {
+        if by_file {
+            return self.to_csv_by_file();
+        }
+        if let Some(directory_tree) = &self.directory_tree {
+            return directory_tree.to_csv();
+        }
+        let mut s = String::from("language,files,size,blank,comment,docs,code,total\n");
+        for stat in self.stats_per_language.iter() {
+            s += &format!("{},{},{},{},{},{},{},{}\n", csv_field(&stat.language), stat.number_of_files, stat.total_size,
+                stat.tot_blank_lines, stat.tot_comment_lines, stat.tot_doc_lines, stat.tot_code_lines, stat.tot_lines);
+        }
+        s += &format!("Total,{},{},{},{},{},{},{}\n", self.number_of_files, self.total_size,
+            self.tot_blank_lines, self.tot_comment_lines, self.tot_doc_lines, self.tot_code_lines, self.tot_lines);
+        s
+    }
+
+    fn to_csv_by_file(&self) -> String {
+        let mut s = String::from("path,language,size,blank,comment,docs,code,total\n");
+        for stat in self.file_stats.iter() {
+            s += &format!("{},{},{},{},{},{},{},{}\n", csv_field(stat.get_path()), csv_field(stat.get_language()), stat.get_size(),
+                stat.get_blank_lines(), stat.get_comment_lines(), stat.get_doc_lines(), stat.get_code_lines(), stat.get_lines());
+        }
+        s
+    }
+
+    pub fn to_markdown(&self, by_file: bool) -> String {
+        if by_file {
+            return self.to_markdown_by_file();
+        }
+        if let Some(directory_tree) = &self.directory_tree {
+            return directory_tree.to_markdown();
         }
+        let mut s = String::from("| Language | Files | Size | Blank | Comment | Docs | Code | Total |\n");
+        s += "|:---------|------:|-----:|------:|--------:|-----:|-----:|------:|\n";
+        for stat in self.stats_per_language.iter() {
+            s += &format!("| {} | {} | {} | {} | {} | {} | {} | {} |\n", stat.language.replace('|', "\\|"), stat.number_of_files,
+                stat.total_size, stat.tot_blank_lines, stat.tot_comment_lines, stat.tot_doc_lines, stat.tot_code_lines, stat.tot_lines);
+        }
+        s += &format!("| **Total** | **{}** | **{}** | **{}** | **{}** | **{}** | **{}** | **{}** |\n", self.number_of_files,
+            self.total_size, self.tot_blank_lines, self.tot_comment_lines, self.tot_doc_lines, self.tot_code_lines, self.tot_lines);
+        s
+    }
+
+    fn to_markdown_by_file(&self) -> String {
+        let mut s = String::from("| Path | Language | Size | Blank | Comment | Docs | Code | Total |\n");
+        s += "|:-----|:---------|-----:|------:|--------:|-----:|-----:|------:|\n";
+        for stat in self.file_stats.iter() {
+            s += &format!("| {} | {} | {} | {} | {} | {} | {} | {} |\n", stat.get_path().replace('|', "\\|"), stat.get_language(),
+                stat.get_size(), stat.get_blank_lines(), stat.get_comment_lines(), stat.get_doc_lines(), stat.get_code_lines(), stat.get_lines());
+        }
+        s
+    }
+
+    pub fn group_by_directory(&mut self, depth: usize) {
+        self.directory_tree = Some(DirectoryTree::new(&self.file_stats, depth));
+    }
+
+    pub fn sort_directories(&mut self, column: SortColumn, reverse: bool) {
+        if let Some(directory_tree) = self.directory_tree.as_mut() {
+            directory_tree.sort(column, reverse);
+        }
+    }
 
+    pub fn to_directory_table(&self) -> Option<String> {
+        self.directory_tree.as_ref().map(|tree| tree.to_table())
     }
 
-    pub fn get_argument<'a>(&self, arg: &str) -> Result<FileStats<'a>, String> {
-        let file = FileHandler::new(&arg);
-        if !file.is_binary() && !EXTENSIONS_TO_IGNORE.contains(&arg.split(".").last().unwrap()) {
-            Ok(file.get_file_stat())
-        } else {
-            Err(format!("{} is not a file or is binary", arg))
+    // Table of the statistics of each file, with the totals of all the files at the
+    // end, even the ones left out by --top
+    pub fn to_file_table(&self) -> String {
+        let mut rows = vec![["File", "Language", "Size", "Blank lines", "Comment lines", "Doc lines", "Code lines", "TOTAL"]
+            .map(String::from).to_vec()];
+        for stat in self.file_stats.iter() {
+            let language = match stat.get_embedded_in() {
+                Some(file_language) => format!("{} in {}", stat.get_language(), file_language),
+                None => stat.get_language().to_string(),
+            };
+            rows.push(vec![
+                stat.get_path().to_string(),
+                language,
+                stat.get_size().to_string(),
+                stat.get_blank_lines().to_string(),
+                stat.get_comment_lines().to_string(),
+                stat.get_doc_lines().to_string(),
+                stat.get_code_lines().to_string(),
+                stat.get_lines().to_string(),
+            ]);
+        }
+        rows.push(vec![
+            match self.file_stats_cut {
+                true => format!("Total (all {} files)", self.number_of_files),
+                false => "Total".to_string(),
+            },
+            String::new(),
+            self.total_size.to_string(),
+            self.tot_blank_lines.to_string(),
+            self.tot_comment_lines.to_string(),
+            self.tot_doc_lines.to_string(),
+            self.tot_code_lines.to_string(),
+            self.tot_lines.to_string(),
+        ]);
+        format_table(&rows, 2, true)
+    }
+
+    // Numbers are sorted from the biggest, names in alphabetical order. Ties are
+    // broken by name so that the output is the same from one run to the next
+    pub fn sort_stats_per_language(&mut self, column: SortColumn, reverse: bool) {
+        let key = |stat: &StatPerLanguage| match column {
+            SortColumn::Files => stat.number_of_files,
+            SortColumn::Size => stat.total_size,
+            SortColumn::Blank => stat.tot_blank_lines,
+            SortColumn::Comment => stat.tot_comment_lines,
+            SortColumn::Docs => stat.tot_doc_lines,
+            SortColumn::Code => stat.tot_code_lines,
+            _ => stat.tot_lines,
+        };
+        match column {
+            SortColumn::Name | SortColumn::Language => self.stats_per_language.sort_by(|a, b| a.language.cmp(&b.language)),
+            _ => self.stats_per_language.sort_by(|a, b| key(b).cmp(&key(a)).then_with(|| a.language.cmp(&b.language))),
+        }
+        if reverse {
+            self.stats_per_language.reverse();
+        }
+    }
+
+    // Same order as sort_stats_per_language, ties are broken by path. top is applied after reverse
+    pub fn sort_file_stats(&mut self, column: SortColumn, reverse: bool, top: Option<usize>) {
+        let by_path = |a: &FileStats, b: &FileStats| a.get_path().cmp(b.get_path());
+        let key = |stat: &FileStats| match column {
+            SortColumn::Size => stat.get_size(),
+            SortColumn::Blank => stat.get_blank_lines(),
+            SortColumn::Comment => stat.get_comment_lines(),
+            SortColumn::Docs => stat.get_doc_lines(),
+            SortColumn::Code => stat.get_code_lines(),
+            SortColumn::Files => 1,
+            _ => stat.get_lines(),
+        };
+        match column {
+            SortColumn::Name => self.file_stats.sort_by(by_path),
+            SortColumn::Language => self.file_stats.sort_by(|a, b| a.get_language().cmp(b.get_language()).then_with(|| by_path(a, b))),
+            _ => self.file_stats.sort_by(|a, b| key(b).cmp(&key(a)).then_with(|| by_path(a, b))),
+        }
+        if reverse {
+            self.file_stats.reverse();
+        }
+        if let Some(top) = top {
+            self.file_stats_cut = self.file_stats.len() > top;
+            self.file_stats.truncate(top);
+        }
+    }
+
+    pub fn get_argument<'a>(&self, file: &FileHandler, mixed: MixedLines) -> Result<Vec<FileStats<'a>>, Skipped> {
+        if EXTENSIONS_TO_IGNORE.contains(&file.get_path().rsplit('.').next().unwrap()) {
+            return Err(Skipped::Ignored);
+        }
+        if file.is_binary()? {
+            return Err(Skipped::Ignored);
+        }
+        match file.read_content()? {
+            Decoded::Text(content) => Ok(file.get_file_stat(&content, mixed)),
+            Decoded::Binary => Err(Skipped::Ignored),
+            Decoded::Undecodable => Err(Skipped::Undecodable),
         }
     }
 
 }
 
+// Table for the terminal, the first row is the header and the first columns are
+// aligned on the left. With total, the last row is set apart like the header
+pub fn format_table(rows: &[Vec<String>], left_aligned: usize, total: bool) -> String {
+    let mut widths = vec![0; rows[0].len()];
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let separator = format!("|{}|\n", "-".repeat(widths.iter().map(|width| width + 3).sum::<usize>() - 1));
+
+    let format_row = |row: &Vec<String>| {
+        let mut line = String::from("|");
+        for (index, (cell, width)) in row.iter().zip(widths.iter()).enumerate() {
+            line += &match index < left_aligned {
+                true => format!(" {:<width$} |", cell, width = width),
+                false => format!(" {:>width$} |", cell, width = width),
+            };
+        }
+        line + "\n"
+    };
+
+    let last = match total {
+        true => rows.len() - 1,
+        false => rows.len(),
+    };
+    let mut s = separator.clone() + &format_row(&rows[0]) + &separator;
+    for row in rows[1..last].iter() {
+        s += &format_row(row);
+    }
+    if total {
+        s += &separator;
+        s += &format_row(&rows[last]);
+    }
+    s + &separator
+}
+
+// Quote the field if it contains a separator, a quote or a new line (RFC 4180)
+pub fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+// The json output of the metric subcommand is made of the structures below and of
+// StatPerLanguage, FileStats and DirectoryStat, whose field names are part of it
+const JSON_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct JsonOutput<'a> {
+    version: u32,
+    totals: JsonTotals,
+    languages: &'a [StatPerLanguage],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    files: Option<&'a [FileStats<'static>]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    directories: Option<Vec<&'a DirectoryStat>>,
+}
+
+#[derive(Serialize)]
+struct JsonTotals {
+    files: usize,
+    ignored_files: usize,
+    undecodable_files: usize,
+    size: usize,
+    blank: usize,
+    comment: usize,
+    docs: usize,
+    code: usize,
+    mixed: usize,
+    lines: usize,
+}
+
+#[derive(Serialize)]
 struct StatPerLanguage {
     language: String,
+    #[serde(rename = "files")]
     number_of_files: usize,
+    #[serde(rename = "size")]
     total_size: usize,
-    tot_lines : usize,
+    #[serde(rename = "blank")]
     tot_blank_lines: usize,
+    #[serde(rename = "comment")]
     tot_comment_lines: usize,
+    #[serde(rename = "docs")]
+    tot_doc_lines: usize,
+    #[serde(rename = "code")]
     tot_code_lines: usize,
+    #[serde(rename = "mixed")]
+    tot_mixed_lines: usize,
+    #[serde(rename = "lines")]
+    tot_lines : usize,
 }
 
 impl StatPerLanguage {
-    fn new(language: String, size: usize, lines: usize, blank_lines: usize, comment_lines: usize, code_line: usize) -> StatPerLanguage {
+    fn new(language: String, size: usize, lines: usize, blank_lines: usize, comment_lines: usize, doc_lines: usize, code_line: usize) -> StatPerLanguage {
         StatPerLanguage {
             language,
             number_of_files: 1,
@@ -121,7 +455,9 @@ impl StatPerLanguage {
             tot_lines : lines,
             tot_blank_lines: blank_lines,
             tot_comment_lines: comment_lines,
+            tot_doc_lines: doc_lines,
             tot_code_lines: code_line,
+            tot_mixed_lines: 0,
         }
     }
 
@@ -145,11 +481,18 @@ impl StatPerLanguage {
         self.tot_comment_lines += lines;
     }
 
+    fn add_tot_doc_lines(&mut self, lines: usize) {
+        self.tot_doc_lines += lines;
+    }
+
     fn add_tot_code_lines(&mut self, lines: usize) {
         self.tot_code_lines += lines;
     }
-}
 
+    fn add_tot_mixed_lines(&mut self, lines: usize) {
+        self.tot_mixed_lines += lines;
+    }
+}
 
 
 
@@ -159,6 +502,7 @@ static mut OUTPUT_NUMBER_OF_FILES_SIZE: isize = -5;
 static mut OUTPUT_SIZE_SIZE: isize = -4;
 static mut OUTPUT_BLANK_SIZE: isize = -11;
 static mut OUTPUT_COMMENT_SIZE: isize = -13;
+static mut OUTPUT_DOC_SIZE: isize = -9;
 static mut OUTPUT_CODE_LINES_SIZE: isize = -10;
 static mut OUTPUT_TOT_LINES_SIZE: isize = -5;
 
@@ -166,26 +510,29 @@ static mut OUTPUT_NUMBER_OF_FILES_PER_LANGUAGE: isize = 0;
 static mut OUTPUT_SIZE_PER_LANGUAGE: isize = 0;
 static mut OUTPUT_BLANK_PER_LANGUAGE: isize = 0;
 static mut OUTPUT_COMMENT_PER_LANGUAGE: isize = 0;
+static mut OUTPUT_DOC_PER_LANGUAGE: isize = 0;
 static mut OUTPUT_CODE_LINES_PER_LANGUAGE: isize = 0;
 static mut OUTPUT_TOT_LINES_PER_LANGUAGE: isize = 0;
 
 impl fmt::Display for ExtractInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>, ) -> fmt::Result {
 
-        let mut size_number_of_files = 0;
-        let mut size_size = 0;
-        let mut size_blank = 0;
-        let mut size_comment = 0;
-        let mut size_code_lines = 0;
-        let mut size_tot_lines = 0;
-        let mut size_hyphen = 0;
-
-        let mut tot_number_of_files = 0;
-        let mut tot_size = 0;
-        let mut tot_blank = 0;
-        let mut tot_comment = 0;
-        let mut tot_code_lines = 0;
-        let mut tot_tot_lines = 0;
+        let mut size_number_of_files;
+        let mut size_size;
+        let mut size_blank;
+        let mut size_comment;
+        let mut size_doc;
+        let mut size_code_lines;
+        let mut size_tot_lines;
+        let mut size_hyphen;
+
+        let mut tot_number_of_files;
+        let mut tot_size;
+        let mut tot_blank;
+        let mut tot_comment;
+        let mut tot_doc;
+        let mut tot_code_lines;
+        let mut tot_tot_lines;
 
         unsafe {
             size_number_of_files = OUTPUT_NUMBER_OF_FILES_SIZE + self.number_of_files.to_string().len() as isize + 1;
@@ -220,6 +567,14 @@ impl fmt::Display for ExtractInfo {
                     OUTPUT_COMMENT_PER_LANGUAGE = OUTPUT_COMMENT_SIZE.abs() + 1;
                 }
             }
+            size_doc = OUTPUT_DOC_SIZE + self.tot_doc_lines.to_string().len() as isize + 1;
+            match size_doc > 1 {
+                true => OUTPUT_DOC_PER_LANGUAGE = OUTPUT_DOC_SIZE.abs() + size_doc,
+                false => {
+                    size_doc = 1;
+                    OUTPUT_DOC_PER_LANGUAGE = OUTPUT_DOC_SIZE.abs() + 1;
+                }
+            }
             size_code_lines = OUTPUT_CODE_LINES_SIZE + self.tot_code_lines.to_string().len() as isize + 1;
             match size_code_lines > 1 {
                 true => OUTPUT_CODE_LINES_PER_LANGUAGE = OUTPUT_CODE_LINES_SIZE.abs() + size_code_lines,
@@ -236,9 +591,9 @@ impl fmt::Display for ExtractInfo {
                     OUTPUT_TOT_LINES_PER_LANGUAGE = OUTPUT_TOT_LINES_SIZE.abs() + 1;
                 }
             }
-            size_hyphen = (size_number_of_files + size_size + size_blank + size_comment + size_code_lines + size_tot_lines).abs();
-            match size_hyphen > 6 {
-                true => size_hyphen = size_hyphen - 5,
+            size_hyphen = (size_number_of_files + size_size + size_blank + size_comment + size_doc + size_code_lines + size_tot_lines).abs();
+            match size_hyphen > 7 {
+                true => size_hyphen -= 6,
                 false => size_hyphen = 1,
             }
 
@@ -262,6 +617,11 @@ impl fmt::Display for ExtractInfo {
                 true => (),
                 false => tot_comment = 1,
             }
+            tot_doc = OUTPUT_DOC_PER_LANGUAGE - self.tot_doc_lines.to_string().len() as isize;
+            match tot_doc > 1 {
+                true => (),
+                false => tot_doc = 1,
+            }
             tot_code_lines = OUTPUT_CODE_LINES_PER_LANGUAGE - self.tot_code_lines.to_string().len() as isize;
             match tot_code_lines > 1 {
                 true => (),
@@ -274,38 +634,45 @@ impl fmt::Display for ExtractInfo {
             }
         }
 
+        let undecodable = match self.number_of_files_undecodable > 0 {
+            true => format!("Number of files skipped (undecodable): {}\n", self.number_of_files_undecodable),
+            false => String::new(),
+        };
+
         let mut s = format!("
 Number of files ignored: {}
-Number of directories: {}
-|----------------------------------------------------------------------------------{}|
-| Language        |{}Files |{}Size |{}Blank lines |{}Comment lines |{}Code lines |{}TOTAL |
-|----------------------------------------------------------------------------------{}|
+{}Number of directories: {}
+|----------------------------------------------------------------------------------------------{}|
+| Language        |{}Files |{}Size |{}Blank lines |{}Comment lines |{}Doc lines |{}Code lines |{}TOTAL |
+|----------------------------------------------------------------------------------------------{}|
 "
-,           self.number_of_files_ignore, self.number_of_directories, 
+,           self.number_of_files_ignore, undecodable, self.number_of_directories, 
             "-".repeat( size_hyphen as usize ),
             " ".repeat( size_number_of_files as usize ),
             " ".repeat( size_size as usize ),
             " ".repeat( size_blank as usize ),
             " ".repeat( size_comment as usize ),
+            " ".repeat( size_doc as usize ),
             " ".repeat( size_code_lines as usize ),
             " ".repeat( size_tot_lines as usize ),
             "-".repeat( size_hyphen as usize ),
             );
 
         for stat in self.stats_per_language.iter() {
-            s += &format!("{}", stat).as_str();
+            s += format!("{}", stat).as_str();
         }
 
         s += format!(
-"|----------------------------------------------------------------------------------{}|
-| Total           |{}{} |{}{} |{}{} |{}{} |{}{} |{}{} |
-|----------------------------------------------------------------------------------{}|
+"|----------------------------------------------------------------------------------------------{}|
+| Total           |{}{} |{}{} |{}{} |{}{} |{}{} |{}{} |{}{} |
+|----------------------------------------------------------------------------------------------{}|
 "
 ,           "-".repeat( size_hyphen as usize ),
             " ".repeat( tot_number_of_files as usize ), self.number_of_files, 
             " ".repeat( tot_size as usize ), self.total_size,
             " ".repeat( tot_blank as usize ), self.tot_blank_lines,
             " ".repeat( tot_comment as usize ), self.tot_comment_lines,
+            " ".repeat( tot_doc as usize ), self.tot_doc_lines,
             " ".repeat( tot_code_lines as usize ), self.tot_code_lines,
             " ".repeat( tot_tot_lines as usize ), self.tot_lines,
             "-".repeat(size_hyphen as usize),
@@ -317,13 +684,14 @@ Number of directories: {}
 impl fmt:: Display for StatPerLanguage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         
-        let mut size_language = 0;
-        let mut size_number_of_files = 0;
-        let mut size_size = 0;
-        let mut size_blank = 0;
-        let mut size_comment = 0;
-        let mut size_code_lines = 0;
-        let mut size_tot_lines = 0;
+        let mut size_language;
+        let mut size_number_of_files;
+        let mut size_size;
+        let mut size_blank;
+        let mut size_comment;
+        let mut size_doc;
+        let mut size_code_lines;
+        let mut size_tot_lines;
 
         unsafe {
             size_language = OUTPUT_LANGUAGE_SIZE.abs() - self.language.len() as isize + 1;
@@ -351,6 +719,11 @@ impl fmt:: Display for StatPerLanguage {
                 true => (),
                 false => size_comment = 1,
             }
+            size_doc = OUTPUT_DOC_PER_LANGUAGE - self.tot_doc_lines.to_string().len() as isize;
+            match size_doc > 1 {
+                true => (),
+                false => size_doc = 1,
+            }
             size_code_lines = OUTPUT_CODE_LINES_PER_LANGUAGE - self.tot_code_lines.to_string().len() as isize;
             match size_code_lines > 1 {
                 true => (),
@@ -366,13 +739,14 @@ impl fmt:: Display for StatPerLanguage {
         }
 
         let s = format!(
-"| {}{}|{}{} |{}{} |{}{} |{}{} |{}{} |{}{} |
+"| {}{}|{}{} |{}{} |{}{} |{}{} |{}{} |{}{} |{}{} |
 "
 ,       self.language, " ".repeat( size_language as usize ),
         " ".repeat( size_number_of_files as usize ), self.number_of_files, 
         " ".repeat( size_size as usize ), self.total_size, 
         " ".repeat( size_blank as usize ), self.tot_blank_lines, 
         " ".repeat( size_comment as usize ), self.tot_comment_lines, 
+        " ".repeat( size_doc as usize ), self.tot_doc_lines, 
         " ".repeat( size_code_lines as usize ), self.tot_code_lines, 
         " ".repeat( size_tot_lines as usize ), self.tot_lines
         );
@@ -1,148 +1,343 @@
 use std::{
-    clone::Clone,
+    env,
+    fs,
+    path::PathBuf,
     collections::{
+    BTreeMap,
     HashMap,
-    HashSet,
 }};
 use regex::Regex;
 use once_cell::sync::Lazy;
+use serde::Deserialize;
 
-#[derive(Clone)]
-pub struct Language<'a> {
-    name: &'a str,
-    single_line_comment: Regex, 
-    block_line_comment_begin: Option<Regex>,
-    block_line_comment_inbetween: Option<Regex>, // TODO needed ? just need to check if the block comment is closed or not
-    block_line_comment_end: Option<Regex>, 
+// How the code of other languages is embedded in the files of a language
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Embedding {
+    // <script> and <style> elements
+    Html,
+    // fenced code blocks
+    Markdown,
+}
+
+// Bundled definitions, the user ones are read from $XDG_CONFIG_HOME/mop/languages.toml
+// (~/.config/mop/languages.toml by default) and replace the bundled ones with the same name
+const BUNDLED_LANGUAGES: &str = include_str!("languages.toml");
+const BUNDLED_HEURISTICS: &str = include_str!("heuristics.toml");
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LanguageDefinition {
+    #[serde(default)]
+    extensions: Vec<String>,
+    #[serde(default)]
+    filenames: Vec<String>,
+    #[serde(default)]
+    interpreters: Vec<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    line_comment: Vec<String>,
+    #[serde(default)]
+    block_comment: Vec<[String; 2]>,
+    #[serde(default)]
+    nested_comments: bool,
+    #[serde(default)]
+    nested_block_comment: Vec<[String; 2]>,
+    #[serde(default)]
+    doc_line_comment: Vec<String>,
+    #[serde(default)]
+    doc_block_comment: Vec<[String; 2]>,
+    #[serde(default)]
+    doc_quotes: Vec<[String; 2]>,
+    #[serde(default)]
+    quotes: Vec<[String; 2]>,
+    #[serde(default)]
+    verbatim_quotes: Vec<[String; 2]>,
+    #[serde(default)]
+    char_literals: bool,
+    embedding: Option<Embedding>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HeuristicsDefinition {
+    disambiguation: Vec<DisambiguationDefinition>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DisambiguationDefinition {
+    extensions: Vec<String>,
+    rules: Vec<HeuristicDefinition>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HeuristicDefinition {
+    language: String,
+    pattern: Option<String>,
+}
+
+pub struct Language {
+    name: String,
+    user_defined: bool,
+    extensions: Vec<String>,
+    filenames: Vec<String>,
+    interpreters: Vec<String>,
+    aliases: Vec<String>,
+    line_comments: Vec<String>,
+    block_comments: Vec<(String, String)>,
+    // whether the block comment of the same index nests
+    nested_block_comments: Vec<bool>,
+    nested_comments: bool,
+    doc_line_comments: Vec<String>,
+    doc_block_comments: Vec<(String, String)>,
+    quotes: Vec<(String, String)>,
+    verbatim_quotes: Vec<(String, String)>,
+    doc_quotes: Vec<(String, String)>,
+    char_literals: bool,
+    embedding: Option<Embedding>,
 }
 
-impl<'a> Language<'a> {
-    pub fn get_name(&self) -> &'a str {
-        self.name
+impl Language {
+    fn new(name: String, definition: LanguageDefinition, user_defined: bool) -> Language {
+        let mut nested_block_comments = vec![definition.nested_comments; definition.block_comment.len()];
+        nested_block_comments.resize(nested_block_comments.len() + definition.nested_block_comment.len(), true);
+        let mut block_comments = pairs(definition.block_comment);
+        block_comments.extend(pairs(definition.nested_block_comment));
+
+        Language {
+            name,
+            user_defined,
+            extensions: definition.extensions,
+            filenames: definition.filenames,
+            interpreters: definition.interpreters,
+            aliases: definition.aliases,
+            line_comments: definition.line_comment,
+            block_comments,
+            nested_block_comments,
+            nested_comments: definition.nested_comments,
+            doc_line_comments: definition.doc_line_comment,
+            doc_block_comments: pairs(definition.doc_block_comment),
+            quotes: pairs(definition.quotes),
+            verbatim_quotes: pairs(definition.verbatim_quotes),
+            doc_quotes: pairs(definition.doc_quotes),
+            char_literals: definition.char_literals,
+            embedding: definition.embedding,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_user_defined(&self) -> bool {
+        self.user_defined
+    }
+
+    pub fn get_line_comments(&self) -> &[String] {
+        &self.line_comments
+    }
+
+    pub fn get_block_comments(&self) -> &[(String, String)] {
+        &self.block_comments
+    }
+
+    pub fn is_block_comment_nested(&self, index: usize) -> bool {
+        self.nested_block_comments[index]
+    }
+
+    // The doc block comments nest like the other block comments of the language
+    pub fn has_nested_comments(&self) -> bool {
+        self.nested_comments
+    }
+
+    pub fn get_doc_line_comments(&self) -> &[String] {
+        &self.doc_line_comments
+    }
+
+    pub fn get_doc_block_comments(&self) -> &[(String, String)] {
+        &self.doc_block_comments
+    }
+
+    pub fn get_quotes(&self) -> &[(String, String)] {
+        &self.quotes
+    }
+
+    pub fn get_verbatim_quotes(&self) -> &[(String, String)] {
+        &self.verbatim_quotes
+    }
+
+    pub fn get_doc_quotes(&self) -> &[(String, String)] {
+        &self.doc_quotes
     }
 
-    pub fn get_single_line_comment(&self) -> &Regex {
-        &self.single_line_comment
+    pub fn has_char_literals(&self) -> bool {
+        self.char_literals
     }
 
-    pub fn get_block_line_comment_begin(&self) -> Option<&Regex> {
-        self.block_line_comment_begin.as_ref()
+    pub fn get_embedding(&self) -> Option<Embedding> {
+        self.embedding
     }
+}
 
-    pub fn get_block_line_comment_inbetween(&self) -> Option<&Regex> {
-        self.block_line_comment_inbetween.as_ref()
+#[derive(Clone)]
+pub struct Heuristic {
+    language: String,
+    pattern: Option<Regex>,
+}
+
+impl Heuristic {
+    pub fn get_language(&self) -> &str {
+        &self.language
     }
 
-    pub fn get_block_line_comment_end(&self) -> Option<&Regex> {
-        self.block_line_comment_end.as_ref()
+    pub fn is_match(&self, content: &str) -> bool {
+        match &self.pattern {
+            Some(pattern) => pattern.is_match(content),
+            None => true,
+        }
     }
+}
+
+fn pairs(delimiters: Vec<[String; 2]>) -> Vec<(String, String)> {
+    delimiters.into_iter().map(|[begin, end]| (begin, end)).collect()
+}
+
+fn user_languages_path() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("mop").join("languages.toml"))
+}
 
+fn parse_definitions(content: &str) -> Result<BTreeMap<String, LanguageDefinition>, toml::de::Error> {
+    toml::from_str(content)
 }
 
-// https://rosettacode.org/wiki/Comments
-// TODO https://dotnetcrunch.in/comments-in-different-programming-languages/
-pub const LANGUAGES: Lazy<HashMap<&str, Language>> = Lazy::new(|| {
+pub static LANGUAGES: Lazy<HashMap<String, Language>> = Lazy::new(|| {
     let mut languages = HashMap::new();
 
-    languages.insert("C", Language {
-        name: "C",
-        single_line_comment: Regex::new(r"^\s*//").unwrap(),
-        block_line_comment_begin: Some(Regex::new(r"\s*/\*").unwrap()),
-        block_line_comment_inbetween: None, 
-        block_line_comment_end: Some(Regex::new(r".*?\*/\s*").unwrap()),
-    });
-
-    languages.insert("C++", Language {
-        name: "C++",
-        single_line_comment: Regex::new(r"^\s*//").unwrap(),
-        block_line_comment_begin: Some(Regex::new(r"\s*/\*").unwrap()),
-        block_line_comment_inbetween: None, 
-        block_line_comment_end: Some(Regex::new(r".*?\*/\s*").unwrap()),
-    });
-
-    languages.insert("C#", Language {
-        name: "C#",
-        single_line_comment: Regex::new(r"^\s*//").unwrap(),
-        block_line_comment_begin: Some(Regex::new(r"\s*/\*").unwrap()),
-        block_line_comment_inbetween: None, 
-        block_line_comment_end: Some(Regex::new(r".*?\*/\s*").unwrap()),
-    });
-
-    languages.insert("Go", Language {
-        name: "Go",
-        single_line_comment: Regex::new(r"^\s*//").unwrap(),
-        block_line_comment_begin: Some(Regex::new(r"\s*/\*").unwrap()),
-        block_line_comment_inbetween: None, 
-        block_line_comment_end: Some(Regex::new(r".*?\*/\s*").unwrap()),
-    });
-
-    languages.insert("Java", Language {
-        name: "Java",
-        single_line_comment: Regex::new(r"^\s*//").unwrap(),
-        block_line_comment_begin: Some(Regex::new(r"\s*/\*").unwrap()),
-        block_line_comment_inbetween: None, 
-        block_line_comment_end: Some(Regex::new(r".*?\*/\s*").unwrap()),
-    });
-
-    languages.insert("Javascript", Language {
-        name: "Javascript",
-        single_line_comment: Regex::new(r"^\s*//").unwrap(),
-        block_line_comment_begin: Some(Regex::new(r"\s*/\*").unwrap()),
-        block_line_comment_inbetween: None, 
-        block_line_comment_end: Some(Regex::new(r".*?\*/\s*").unwrap()),
-    });
-
-    languages.insert("Kotlin", Language {
-        name: "Kotlin",
-        single_line_comment: Regex::new(r"^\s*//").unwrap(),
-        block_line_comment_begin: Some(Regex::new(r"\s*/\*").unwrap()),
-        block_line_comment_inbetween: None, 
-        block_line_comment_end: Some(Regex::new(r".*?\*/\s*").unwrap()),
-    });
-
-    languages.insert("Python", Language {
-        name: "Python",
-        single_line_comment: Regex::new(r"^\s*#").unwrap(),
-        block_line_comment_begin: None,
-        block_line_comment_inbetween: None, 
-        block_line_comment_end: None,
-    });
-
-    languages.insert("Rust", Language {
-        name: "Rust",
-        single_line_comment: Regex::new(r"^\s*//").unwrap(),
-        block_line_comment_begin: Some(Regex::new(r"\s*/\*").unwrap()),
-        block_line_comment_inbetween: None, 
-        block_line_comment_end: Some(Regex::new(r".*?\*/\s*").unwrap()),
-    });
-
-    languages.insert("Swift", Language {
-        name: "Swift",
-        single_line_comment: Regex::new(r"^\s*//").unwrap(),
-        block_line_comment_begin: Some(Regex::new(r"\s*/\*").unwrap()),
-        block_line_comment_inbetween: None, 
-        block_line_comment_end: Some(Regex::new(r".*?\*/\s*").unwrap()),
-    });
-
-    languages.insert("Typescript", Language {
-        name: "Typescript",
-        single_line_comment: Regex::new(r"^\s*//").unwrap(),
-        block_line_comment_begin: Some(Regex::new(r"\s*/\*").unwrap()),
-        block_line_comment_inbetween: None, 
-        block_line_comment_end: Some(Regex::new(r".*?\*/\s*").unwrap()),
-    });
+    let bundled = parse_definitions(BUNDLED_LANGUAGES).expect("the bundled languages.toml is invalid");
+    for (name, definition) in bundled {
+        languages.insert(name.clone(), Language::new(name, definition, false));
+    }
+
+    if let Some(path) = user_languages_path() {
+        if let Ok(content) = fs::read_to_string(&path) {
+            match parse_definitions(&content) {
+                Ok(user) => {
+                    for (name, definition) in user {
+                        languages.insert(name.clone(), Language::new(name, definition, true));
+                    }
+                }
+                Err(e) => eprintln!("warning: {} is ignored: {}", path.display(), e),
+            }
+        }
+    }
 
     languages
 });
 
-pub const EXTENSIONS: Lazy<HashMap<&str, HashSet<&str>>> = Lazy::new(|| { // TODO This need to be updated
-    let mut extensions = HashMap::new();
+// Language names sorted with the user defined ones last, so that they win when
+// two languages claim the same extension, file name, interpreter or alias
+fn languages_by_priority() -> Vec<&'static Language> {
+    let mut languages = LANGUAGES.values().collect::<Vec<_>>();
+    languages.sort_by(|a, b| a.user_defined.cmp(&b.user_defined).then_with(|| a.name.cmp(&b.name)));
+    languages
+}
+
+// extension -> rules to choose between the languages sharing it, tried in order
+pub static HEURISTICS: Lazy<HashMap<String, Vec<Heuristic>>> = Lazy::new(|| {
+    let definition: HeuristicsDefinition = toml::from_str(BUNDLED_HEURISTICS).expect("the bundled heuristics.toml is invalid");
 
-    extensions.insert("C++", HashSet::from_iter(vec!["cpp", "cc", "C"]));
-    extensions.insert("Java", HashSet::from_iter(vec!["java"]));
-    extensions.insert("Rust", HashSet::from_iter(vec!["rs"]));
+    let mut heuristics = HashMap::new();
+    for disambiguation in definition.disambiguation {
+        let rules = disambiguation.rules.iter().map(|rule| Heuristic {
+            language: rule.language.clone(),
+            pattern: rule.pattern.as_ref().map(|pattern| Regex::new(&format!("(?m){}", pattern)).unwrap()),
+        }).collect::<Vec<_>>();
+        for extension in disambiguation.extensions {
+            heuristics.insert(extension, rules.clone());
+        }
+    }
+    heuristics
+});
 
+// extension -> language name
+pub static EXTENSIONS: Lazy<HashMap<&str, &str>> = Lazy::new(|| {
+    let mut extensions = HashMap::new();
+    for language in languages_by_priority() {
+        for extension in language.extensions.iter() {
+            extensions.insert(extension.as_str(), language.name.as_str());
+        }
+    }
     extensions
 });
 
+// file name -> language name
+pub static FILENAMES: Lazy<HashMap<&str, &str>> = Lazy::new(|| {
+    let mut filenames = HashMap::new();
+    for language in languages_by_priority() {
+        for filename in language.filenames.iter() {
+            filenames.insert(filename.as_str(), language.name.as_str());
+        }
+    }
+    filenames
+});
+
+// interpreter -> language name
+pub static INTERPRETERS: Lazy<HashMap<&str, &str>> = Lazy::new(|| {
+    let mut interpreters = HashMap::new();
+    for language in languages_by_priority() {
+        for interpreter in language.interpreters.iter() {
+            interpreters.insert(interpreter.as_str(), language.name.as_str());
+        }
+    }
+    interpreters
+});
+
+// lowercase name or alias used in the vim and emacs modelines -> language name
+pub static MODELINE_NAMES: Lazy<HashMap<String, &str>> = Lazy::new(|| {
+    let mut names = HashMap::new();
+    for language in languages_by_priority() {
+        names.insert(language.name.to_lowercase(), language.name.as_str());
+    }
+    // an alias wins over the name of another language
+    for language in languages_by_priority() {
+        for alias in language.aliases.iter() {
+            names.insert(alias.to_lowercase(), language.name.as_str());
+        }
+    }
+    names
+});
+
 pub const EXTENSIONS_TO_IGNORE: [&str; 65] = ["pdf", "png", "jpg", "jpeg", "gif", "svg", "ico", "bmp", "tiff", "tif", "webp", "psd", "eps", "raw", "cr2", "nef", "orf", "sr2", "arw", "dng", "heic", "heif", "indd", "zip", "rar", "tar", "gz", "7z", "bz2", "dmg", "iso", "mp3", "mp4", "wav", "flac", "aac", "ogg", "wma", "m4a", "avi", "mov", "wmv", "mpg", "mpeg", "3gp", "mkv", "flv", "swf", "f4v", "f4p", "f4a", "f4b", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp", "bluej", "class", "jar", "out" ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_catalogue() {
+        let bundled = parse_definitions(BUNDLED_LANGUAGES).unwrap();
+        assert!(bundled.len() >= 100, "{} languages", bundled.len());
+        let heuristics: HeuristicsDefinition = toml::from_str(BUNDLED_HEURISTICS).unwrap();
+        let disambiguated = heuristics.disambiguation.iter().flat_map(|d| d.extensions.iter()).collect::<Vec<_>>();
+
+        // an extension shared by several languages needs heuristics to choose between them
+        let mut claimed = HashMap::new();
+        for (name, definition) in bundled.iter() {
+            for extension in definition.extensions.iter() {
+                if let Some(other) = claimed.insert(extension, name) {
+                    assert!(disambiguated.contains(&extension), ".{} is claimed by {} and {}", extension, other, name);
+                }
+            }
+        }
+        for rule in heuristics.disambiguation.iter().flat_map(|d| d.rules.iter()) {
+            assert!(bundled.contains_key(&rule.language), "unknown language {} in heuristics.toml", rule.language);
+        }
+    }
+}
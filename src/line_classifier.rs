@@ -0,0 +1,266 @@
+use crate::languages_mapping::Language;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LineKind {
+    Blank,
+    Comment,
+    Doc,
+    Code,
+    // code followed or preceded by a comment or some documentation
+    Mixed,
+}
+
+struct LineComment<'a> {
+    begin: &'a str,
+    doc: bool,
+}
+
+struct BlockComment<'a> {
+    begin: &'a str,
+    end: &'a str,
+    nested: bool,
+    doc: bool,
+}
+
+struct Quote<'a> {
+    begin: &'a str,
+    end: &'a str,
+    // a backslash escapes the next character, except in the verbatim strings
+    escapes: bool,
+    // a docstring, only when the quote starts its line
+    doc: bool,
+}
+
+// Where the previous line left us, the index is the one of the delimiter that
+// opened the comment or the string, and the depth the number of nested
+// comments still open
+#[derive(Clone, Copy)]
+enum State {
+    Code,
+    BlockComment(usize, usize),
+    Quote(usize),
+}
+
+#[derive(Clone, Copy)]
+enum Token {
+    LineComment(usize),
+    BlockComment(usize),
+    Quote(usize),
+}
+
+// Lexer reading a file line by line and keeping track of the comments and the
+// strings spanning several lines. A line is code as soon as it has something
+// outside of a comment, a string being code, and mixed if it also has a
+// comment. Otherwise, it is a doc line when it has some documentation and a
+// comment line when it only has comments
+pub struct LineClassifier<'a> {
+    line_comments: Vec<LineComment<'a>>,
+    block_comments: Vec<BlockComment<'a>>,
+    quotes: Vec<Quote<'a>>,
+    char_literals: bool,
+    state: State,
+    // bytes a delimiter can start with, to skip the others quickly
+    starts: [bool; 256],
+}
+
+impl<'a> LineClassifier<'a> {
+    pub fn new(language: &'a Language) -> LineClassifier<'a> {
+        let line_comments = language.get_line_comments().iter()
+            .map(|begin| LineComment { begin, doc: false })
+            .chain(language.get_doc_line_comments().iter().map(|begin| LineComment { begin, doc: true }))
+            .collect::<Vec<_>>();
+        let block_comments = language.get_block_comments().iter().enumerate()
+            .map(|(index, (begin, end))| BlockComment { begin, end, nested: language.is_block_comment_nested(index), doc: false })
+            .chain(language.get_doc_block_comments().iter()
+                .map(|(begin, end)| BlockComment { begin, end, nested: language.has_nested_comments(), doc: true }))
+            .collect::<Vec<_>>();
+        let quotes = language.get_quotes().iter()
+            .map(|(begin, end)| Quote { begin, end, escapes: true, doc: false })
+            .chain(language.get_verbatim_quotes().iter().map(|(begin, end)| Quote { begin, end, escapes: false, doc: false }))
+            .chain(language.get_doc_quotes().iter().map(|(begin, end)| Quote { begin, end, escapes: true, doc: true }))
+            .collect::<Vec<_>>();
+
+        let mut starts = [false; 256];
+        let delimiters = line_comments.iter().map(|comment| comment.begin)
+            .chain(block_comments.iter().map(|comment| comment.begin))
+            .chain(quotes.iter().map(|quote| quote.begin));
+        for delimiter in delimiters {
+            if let Some(byte) = delimiter.bytes().next() {
+                starts[byte as usize] = true;
+            }
+        }
+        if language.has_char_literals() {
+            starts[b'\'' as usize] = true;
+        }
+
+        LineClassifier {
+            line_comments,
+            block_comments,
+            quotes,
+            char_literals: language.has_char_literals(),
+            state: State::Code,
+            starts,
+        }
+    }
+
+    pub fn classify(&mut self, line: &str) -> LineKind {
+        if line.trim().is_empty() {
+            return LineKind::Blank;
+        }
+        let bytes = line.as_bytes();
+        let mut has_code = false;
+        let mut has_comment = false;
+        let mut has_doc = false;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match self.state {
+                State::Code => {
+                    let byte = bytes[i];
+                    if byte.is_ascii_whitespace() {
+                        i += 1;
+                        continue;
+                    }
+                    if !self.starts[byte as usize] {
+                        has_code = true;
+                        i += 1;
+                        continue;
+                    }
+                    let line_start = !has_code && !has_comment && !has_doc;
+                    match self.next_token(&bytes[i..], line_start) {
+                        Some((Token::LineComment(index), _)) => {
+                            match self.line_comments[index].doc {
+                                true => has_doc = true,
+                                false => has_comment = true,
+                            }
+                            break;
+                        }
+                        Some((Token::BlockComment(index), length)) => {
+                            match self.block_comments[index].doc {
+                                true => has_doc = true,
+                                false => has_comment = true,
+                            }
+                            self.state = State::BlockComment(index, 1);
+                            i += length;
+                        }
+                        Some((Token::Quote(index), length)) => {
+                            match self.quotes[index].doc {
+                                true => has_doc = true,
+                                false => has_code = true,
+                            }
+                            self.state = State::Quote(index);
+                            i += length;
+                        }
+                        None => {
+                            has_code = true;
+                            i += match byte == b'\'' && self.char_literals {
+                                true => char_literal_length(&bytes[i..]).unwrap_or(1),
+                                false => 1,
+                            };
+                        }
+                    }
+                }
+                State::BlockComment(index, depth) => {
+                    let comment = &self.block_comments[index];
+                    match comment.doc {
+                        true => has_doc = true,
+                        false => has_comment = true,
+                    }
+                    if bytes[i..].starts_with(comment.end.as_bytes()) {
+                        self.state = match depth {
+                            1 => State::Code,
+                            _ => State::BlockComment(index, depth - 1),
+                        };
+                        i += comment.end.len();
+                    } else if comment.nested && bytes[i..].starts_with(comment.begin.as_bytes()) {
+                        self.state = State::BlockComment(index, depth + 1);
+                        i += comment.begin.len();
+                    } else {
+                        i += 1;
+                    }
+                }
+                State::Quote(index) => {
+                    let quote = &self.quotes[index];
+                    match quote.doc {
+                        true => has_doc = true,
+                        false => has_code = true,
+                    }
+                    if quote.escapes && bytes[i] == b'\\' {
+                        i += 2;
+                    } else if bytes[i..].starts_with(quote.end.as_bytes()) {
+                        self.state = State::Code;
+                        i += quote.end.len();
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+        }
+
+        match (has_code, has_doc, has_comment) {
+            (true, false, false) => LineKind::Code,
+            (true, _, _) => LineKind::Mixed,
+            (false, true, _) => LineKind::Doc,
+            _ => LineKind::Comment,
+        }
+    }
+
+    // The longest delimiter starting the text, so that --[[ is not read as -- in Lua.
+    // The doc quotes are only looked for at the start of the line, and a doc comment
+    // followed by its last character again, like //// or /*****, is a banner comment
+    fn next_token(&self, text: &[u8], line_start: bool) -> Option<(Token, usize)> {
+        let mut best: Option<(Token, usize)> = None;
+        let mut consider = |token: Token, delimiter: &str| {
+            let longer = best.is_none_or(|(_, length)| delimiter.len() > length);
+            if longer && !delimiter.is_empty() && text.starts_with(delimiter.as_bytes()) {
+                best = Some((token, delimiter.len()));
+            }
+        };
+        let banner = |delimiter: &str| text.get(delimiter.len()).is_some_and(|&byte| delimiter.bytes().last() == Some(byte));
+
+        // first, so that a docstring wins over the string with the same delimiter
+        for (index, quote) in self.quotes.iter().enumerate() {
+            if quote.doc && line_start {
+                consider(Token::Quote(index), quote.begin);
+            }
+        }
+        for (index, comment) in self.line_comments.iter().enumerate() {
+            if !(comment.doc && banner(comment.begin)) {
+                consider(Token::LineComment(index), comment.begin);
+            }
+        }
+        for (index, comment) in self.block_comments.iter().enumerate() {
+            // /**/ is an empty comment, not the start of a doc comment
+            let empty = text.starts_with(comment.begin.as_bytes()) && text[comment.begin.len()..].starts_with(comment.end.as_bytes());
+            if empty && !comment.doc {
+                return Some((Token::BlockComment(index), comment.begin.len()));
+            }
+            if !(comment.doc && banner(comment.begin)) {
+                consider(Token::BlockComment(index), comment.begin);
+            }
+        }
+        for (index, quote) in self.quotes.iter().enumerate() {
+            if !quote.doc {
+                consider(Token::Quote(index), quote.begin);
+            }
+        }
+        best
+    }
+}
+
+// 'a', '"' or '\u{1F600}', but not the 'a of a Rust lifetime or an OCaml type variable
+fn char_literal_length(text: &[u8]) -> Option<usize> {
+    let length = match text.get(1)? {
+        b'\\' => return text.iter().skip(3).take(10).position(|&byte| byte == b'\'').map(|position| position + 4),
+        b'\'' => return None,
+        // bytes of the utf-8 character
+        0xf0.. => 4,
+        0xe0.. => 3,
+        0xc0.. => 2,
+        _ => 1,
+    };
+    match text.get(1 + length) {
+        Some(b'\'') => Some(length + 2),
+        _ => None,
+    }
+}
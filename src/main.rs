@@ -1,26 +1,29 @@
-use std::env::args;
+use std::process::ExitCode;
 
+pub mod cli;
 pub mod entry_point;
 
+pub mod directory_tree;
+pub mod embedded;
+pub mod encoding;
+pub mod error;
+pub mod file_filter;
 pub mod file_handler;
 pub mod languages_mapping;
+pub mod line_classifier;
 pub mod file_supplier;
 pub mod search;
 pub mod search_print;
 
-fn main() {
-    let mut args = args().skip(1).collect::<Vec<_>>();
+use cli::{Cli, Command};
 
-    let mut args_itr = args.iter();
-    if let Some(arg) = args_itr.next() {
-        match arg.as_str() {
-            "-h" | "--help" => (), // todo add help
-            "-m" | "--metric" => entry_point::get_stat(args.split_off(1)),
-            "-t" | "--todo" => entry_point::search_for(args),
-            "-s" | "--search" => entry_point::search_for(args.split_off(1)),
-            "-b" => (),
-            _ => (),
-        }
-    }
+fn main() -> ExitCode {
+    let cli = Cli::parse_args();
 
+    let code = match cli.command {
+        Command::Metric(args) => entry_point::get_stat(&args),
+        Command::Search(args) => entry_point::search_for(&args),
+        Command::Todo(args) => entry_point::search_todo(&args),
+    };
+    ExitCode::from(code)
 }
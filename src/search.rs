@@ -1,56 +1,112 @@
+use std::collections::{BTreeSet, HashMap};
 use std::fmt;
+use std::io;
+use std::ops::Range;
 use std::sync::Mutex;
 
+use crate::cli::SearchArgs;
+use crate::encoding::Decoded;
+use crate::error::PathError;
 use crate::file_handler::FileHandler;
-use crate::languages_mapping::EXTENSIONS_TO_IGNORE;
-use crate::search_print::print_and_choose;
+use crate::languages_mapping::{Language, EXTENSIONS_TO_IGNORE, LANGUAGES};
+use crate::search_print::{print_and_choose, print_results};
 
 use rayon::prelude::*;
+use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
 use termion::{color, style};
 
 pub struct Search<'a> {
-    files: Vec<String>,
+    files: Vec<FileHandler>,
+    // as given on the command line, to be displayed
     pattern: &'a str,
+    // None to search for the todo comments
+    matcher: Option<Matcher>,
+    // number of lines shown before and after each match
+    context: (usize, usize),
     search_result: Vec<SearchResult>,
+    errors: Vec<PathError>,
 }
 
 impl<'a> Search<'a> {
-    pub fn new(files: Vec<String>, pattern: &str) -> Search {
+    pub fn new(files: Vec<FileHandler>, pattern: &'a str, matcher: Option<Matcher>) -> Search<'a> {
         Search { 
             files,
             pattern,
+            matcher,
+            context: (0, 0),
             search_result: Vec::new(),
+            errors: Vec::new(),
         }
     }
 
-    pub fn give_and_output_search(&mut self) {
-        if self.pattern == "TODO" {
-            self.search_todo();
-        } else {
-            self.search_pattern();
+    pub fn give_search(&mut self) {
+        match self.matcher.take() {
+            Some(matcher) => {
+                self.search_pattern(&matcher);
+                self.matcher = Some(matcher);
+            }
+            None => self.search_todo(),
         }
-        print_and_choose(&self.search_result);
+    }
+
+    pub fn add_context(&mut self, before: usize, after: usize) {
+        self.context = (before, after);
+    }
+
+    // The files that could not be read during the search
+    pub fn take_errors(&mut self) -> Vec<PathError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    pub fn output_search(&self) -> bool {
+        if self.search_result.is_empty() {
+            println!("No match found for {}", self.pattern);
+            return false;
+        }
+        // the interactive list reads the keys from the terminal
+        match termion::is_tty(&io::stdout()) {
+            true => print_and_choose(&self.search_result),
+            false => print_results(&self.search_result),
+        }
+        true
     }
 
     fn search_todo(&mut self) {
         let search_result_vec = Mutex::new(Vec::new());
-        self.files.par_iter().for_each(|file| {
-            let file_handler = FileHandler::new(file);
-            if !file_handler.is_binary() && !EXTENSIONS_TO_IGNORE.contains(&file.split(".").last().unwrap()) {
-                match file_handler.get_language_for_search() {
+        let errors = Mutex::new(Vec::new());
+        // one matcher per comment delimiter, shared by the languages using it
+        let matchers = LANGUAGES.values().filter_map(todo_comment).collect::<BTreeSet<_>>().into_iter()
+            .filter_map(|comment| {
+                let pattern = comment.to_owned() + " TODO"; // TODO make also work for todo (lowercase)
+                let regex = Regex::new(&format!("{}|{}", regex::escape(&pattern), regex::escape(&pattern.to_lowercase()))).ok()?;
+                Some((comment, Matcher::new(regex)))
+            })
+            .collect::<HashMap<_, _>>();
+        // each file is dropped, with its content, once searched
+        std::mem::take(&mut self.files).into_par_iter().for_each(|file_handler| {
+            let file = file_handler.get_path();
+            if !EXTENSIONS_TO_IGNORE.contains(&file.rsplit('.').next().unwrap()) {
+                // read before the language detection, which then reuses it
+                let content = match read_text(&file_handler) {
+                    Ok(Some(content)) => content,
+                    Ok(None) => return,
+                    Err(error) => return errors.lock().unwrap().push(error),
+                };
+                match file_handler.get_language() {
                     Some(language) => {
-                        let single_comment = language.get_single_line_comment().to_string();
-                        let single_comment = single_comment.split("*").last().unwrap();
-                        let pattern = single_comment.to_owned() + " TODO"; // TODO make also work for todo (lowercase)
-                        let result = file_handler.search_pattern(pattern.as_str());
+                        let matcher = match todo_comment(language).and_then(|comment| matchers.get(comment)) {
+                            Some(matcher) => matcher,
+                            None => return,
+                        };
+                        let result = file_handler.search_pattern(&content, matcher);
                         match result.is_empty() {
                             true  => (),
                             false  => {
                                 let mut search_result = SearchResult::new(file.to_string());
-                                for i in 0..result.len() {
-                                    let just_todo = result[i].1.split("TODO").last().unwrap().trim().to_string();
-                                    let new_result = (result[i].0, just_todo);
-                                    search_result.add_lines(new_result);
+                                for mut line in result {
+                                    line.text = line.text.split("TODO").last().unwrap().trim().to_string();
+                                    line.spans.clear();
+                                    search_result.add_lines(line);
                                 }
                                 search_result_vec.lock().unwrap().push(search_result);
                             },
@@ -65,69 +121,421 @@ impl<'a> Search<'a> {
         for item in search_result_vec.lock().unwrap().iter() {
             self.search_result.push(item.to_owned());
         }
+        self.errors.extend(errors.into_inner().unwrap());
     }
 
-    fn search_pattern(&mut self) {
+    fn search_pattern(&mut self, matcher: &Matcher) {
         let search_result_vec = Mutex::new(Vec::new());
-        self.files.par_iter().for_each(|file| {
-            let file_handler = FileHandler::new(file);
-            if !file_handler.is_binary() {
-                let result =file_handler.search_pattern(self.pattern);
-                match result.is_empty() {
-                    true  => (),
-                    false  => {
-                        let mut search_result = SearchResult::new(file.to_string());
-                        for i in 0..result.len() {
-                            search_result.add_lines(result[i].to_owned())  
-                        }
-                        search_result_vec.lock().unwrap().push(search_result);
-                    },
-                }
-            } 
+        let errors = Mutex::new(Vec::new());
+        std::mem::take(&mut self.files).into_par_iter().for_each(|file_handler| {
+            let file = file_handler.get_path();
+            let content = match read_text(&file_handler) {
+                Ok(Some(content)) => content,
+                Ok(None) => return,
+                Err(error) => return errors.lock().unwrap().push(error),
+            };
+            let result =file_handler.search_pattern(&content, matcher);
+            match result.is_empty() {
+                true  => (),
+                false  => {
+                    let mut search_result = SearchResult::new(file.to_string());
+                    search_result.add_context(context_lines(&content, &result, self.context));
+                    for line in result {
+                        search_result.add_lines(line)
+                    }
+                    search_result_vec.lock().unwrap().push(search_result);
+                },
+            }
         });
         for item in search_result_vec.lock().unwrap().iter() {
             self.search_result.push(item.to_owned());
         }
+        self.errors.extend(errors.into_inner().unwrap());
+    }
+}
+
+// The lines matching one of the patterns, all the --and patterns and none of
+// the --not ones. The patterns are compiled together, so that each line is read
+// once whatever their number
+pub struct Matcher {
+    regex: Regex,
+    and: RegexSet,
+    not: RegexSet,
+    // matches the whole file instead of each line, the --and and --not patterns
+    // then apply to the text of each match
+    multiline: bool,
+}
+
+impl Matcher {
+    pub fn new(regex: Regex) -> Matcher {
+        Matcher {
+            regex,
+            and: RegexSet::empty(),
+            not: RegexSet::empty(),
+            multiline: false,
+        }
+    }
+
+    // PATTERN is taken literally, the -e, -f, --and and --not patterns are regexes
+    // unless -F is given. patterns are the ones of -e and -f. The search is case
+    // sensitive by default, and with --smart-case each list of patterns is case
+    // insensitive unless one of them has an uppercase letter
+    pub fn from_args(args: &SearchArgs, patterns: &[String]) -> Result<Matcher, regex::Error> {
+        // the regexes of a list, and whether its patterns have an uppercase letter
+        let to_regexes = |patterns: &[String], literal: bool| {
+            let uppercase = patterns.iter().any(|pattern| has_uppercase(pattern, literal));
+            let regexes = patterns.iter().map(|pattern| match literal {
+                true => regex::escape(pattern),
+                false => pattern.to_owned(),
+            }).collect::<Vec<_>>();
+            (regexes, uppercase)
+        };
+        let (patterns, patterns_uppercase) = match patterns.is_empty() {
+            true => to_regexes(args.pattern.as_slice(), true),
+            false => to_regexes(patterns, args.fixed_strings),
+        };
+        let (and, and_uppercase) = to_regexes(&args.and, args.fixed_strings);
+        let (not, not_uppercase) = to_regexes(&args.not, args.fixed_strings);
+        let ignore_case = |uppercase: bool| args.ignore_case || (args.smart_case && !uppercase);
+
+        let mut pattern = patterns.iter().map(|pattern| format!("(?:{})", pattern)).collect::<Vec<_>>().join("|");
+        // like ripgrep, no word character right before or after the match, which
+        // unlike \b also finds the patterns starting or ending with a punctuation
+        if args.word {
+            pattern = format!(r"\b{{start-half}}(?:{})\b{{end-half}}", pattern);
+        }
+        // with --multiline, ^ and $ still match at the start and the end of each line
+        let regex = RegexBuilder::new(&pattern).case_insensitive(ignore_case(patterns_uppercase)).multi_line(args.multiline).build()?;
+        let mut matcher = Matcher::new(regex);
+        matcher.multiline = args.multiline;
+        matcher.and = RegexSetBuilder::new(&and).case_insensitive(ignore_case(and_uppercase)).build()?;
+        matcher.not = RegexSetBuilder::new(&not).case_insensitive(ignore_case(not_uppercase)).build()?;
+        Ok(matcher)
+    }
+
+    pub fn is_multiline(&self) -> bool {
+        self.multiline
+    }
+
+    fn keeps(&self, text: &str) -> bool {
+        (self.and.is_empty() || self.and.matches(text).matched_all()) && !self.not.is_match(text)
+    }
+
+    // Byte offsets of the matches in the line, none when the line is not kept
+    pub fn find(&self, line: &str) -> Vec<Range<usize>> {
+        match self.keeps(line) {
+            true => self.regex.find_iter(line).map(|found| found.range()).collect(),
+            false => Vec::new(),
+        }
+    }
+
+    // Byte offsets of the matches in the whole content, for --multiline
+    pub fn find_all(&self, content: &str) -> Vec<Range<usize>> {
+        self.regex.find_iter(content)
+            .filter(|found| self.keeps(found.as_str()))
+            .map(|found| found.range())
+            .collect()
+    }
+}
+
+// The lines around the matches which are not matches themselves, in order
+fn context_lines(content: &str, matches: &[SearchLine], (before, after): (usize, usize)) -> Vec<(u32, String)> {
+    if before == 0 && after == 0 {
+        return Vec::new();
+    }
+    let lines = content.lines().collect::<Vec<_>>();
+    let mut numbers = BTreeSet::new();
+    for found in matches {
+        let (start, end) = (found.line_number as usize - 1, found.end_line_number as usize - 1);
+        numbers.extend(start.saturating_sub(before)..(end + after + 1).min(lines.len()));
+    }
+    for found in matches {
+        for index in found.line_number as usize - 1..found.end_line_number as usize {
+            numbers.remove(&index);
+        }
+    }
+    numbers.into_iter().map(|index| (index as u32 + 1, lines[index].trim().to_owned())).collect()
+}
+
+// Whether a pattern has an uppercase letter, for the smart case. In a regex, the
+// letter of an escape like \S, \W, \p{Lu} or \x4F is not one, but the letter
+// after an escaped backslash is
+fn has_uppercase(pattern: &str, literal: bool) -> bool {
+    if literal {
+        return pattern.chars().any(char::is_uppercase);
+    }
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                // the name or the digits of a class or a code point, braced or not
+                let skipped = match chars.next() {
+                    Some('p' | 'P') => 1,
+                    Some('x') => 2,
+                    Some('u') => 4,
+                    Some('U') => 8,
+                    _ => 0,
+                };
+                match chars.clone().next() {
+                    Some('{') if skipped > 0 => {
+                        chars.find(|&c| c == '}');
+                    }
+                    _ => chars.by_ref().take(skipped).for_each(drop),
+                }
+            }
+            c if c.is_uppercase() => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
+// The delimiter of the todo comments, the first line comment of the language or
+// the start of its first block comment
+fn todo_comment(language: &Language) -> Option<&str> {
+    match language.get_line_comments().first() {
+        Some(comment) => Some(comment),
+        None => language.get_block_comments().first().map(|(begin, _)| begin.as_str()),
+    }
+}
+
+// The decoded content of a text file, None for a binary or undecodable one
+fn read_text(file_handler: &FileHandler) -> Result<Option<String>, PathError> {
+    if file_handler.is_binary()? {
+        return Ok(None);
+    }
+    match file_handler.read_content()? {
+        Decoded::Text(content) => Ok(Some(content)),
+        _ => Ok(None),
+    }
+}
+
+// A line with a match
+#[derive(Clone)]
+pub struct SearchLine {
+    pub line_number: u32,
+    // last line of the match with --multiline, line_number otherwise
+    pub end_line_number: u32,
+    // column of the first match, from 1 and in characters, for the editor
+    pub column: usize,
+    // the line without its leading and trailing spaces
+    pub text: String,
+    // byte offsets of the matches in text, to highlight them
+    pub spans: Vec<Range<usize>>,
+}
+
+impl SearchLine {
+    // first_match and matches are byte offsets in line, a match may go past its end
+    // or even start after it, on the line break with --multiline
+    pub fn new(line_number: u32, line: &str, first_match: usize, matches: &[Range<usize>]) -> SearchLine {
+        let text = line.trim();
+        let start = line.len() - line.trim_start().len();
+        let spans = matches.iter()
+            .map(|found| found.start.saturating_sub(start).min(text.len())..found.end.saturating_sub(start).min(text.len()))
+            .filter(|span| !span.is_empty())
+            .collect();
+        SearchLine {
+            line_number,
+            end_line_number: line_number,
+            column: line[..first_match.min(line.len())].chars().count() + 1,
+            text: text.to_owned(),
+            spans,
+        }
+    }
+
+    pub fn add_end_line_number(&mut self, end_line_number: u32) {
+        self.end_line_number = self.end_line_number.max(end_line_number);
+    }
+
+    // text with its matches highlighted, or as is without colors
+    fn highlighted(&self, colored: bool) -> String {
+        if !colored {
+            return self.text.clone();
+        }
+        let mut s = String::new();
+        let mut end = 0;
+        for span in self.spans.iter() {
+            s += format!("{}{}{}{}{}{}", &self.text[end..span.start], color::Fg(color::Red), style::Bold,
+                &self.text[span.clone()], style::Reset, color::Fg(color::Reset)).as_str();
+            end = span.end;
+        }
+        s + &self.text[end..]
     }
 }
 
 #[derive(Clone)]
 pub struct SearchResult {
     pub file_name: String,
-    pub lines: Vec<(u32, String)>, // for search_print
+    pub lines: Vec<SearchLine>, // for search_print
+    // lines shown around the matches with -A, -B or -C
+    pub context: Vec<(u32, String)>,
 }
 
-impl<'a> SearchResult {
+impl SearchResult {
     pub fn new(file_name: String) -> SearchResult {
         SearchResult {
             file_name,
             lines: Vec::new(),
+            context: Vec::new(),
         }
     }
     
-    fn add_lines(&mut self, value: (u32, String) ) {
+    fn add_lines(&mut self, value: SearchLine) {
         self.lines.push(value);
     }
+
+    fn add_context(&mut self, context: Vec<(u32, String)>) {
+        self.context = context;
+    }
+
+    // The file name and its lines, with the colors of the terminal or without
+    // them when the output is not one
+    pub fn to_text(&self, colored: bool) -> String {
+        let paint = |style: &dyn fmt::Display| match colored {
+            true => style.to_string(),
+            false => String::new(),
+        };
+        let mut s = format!("{}{}{}{}{}\n", paint(&color::Fg(color::Green)), 
+                                                    paint(&style::Bold), 
+                                                    self.file_name, 
+                                                    paint(&style::Reset), 
+                                                    paint(&color::Fg(color::Reset))
+                                                    );
+        // the matches (with their index) and their context by line number, with
+        // -- between the groups of lines that don't follow each other
+        let mut lines = self.lines.iter().enumerate().map(|(index, found)| (found.line_number, Some(index), found.highlighted(colored)))
+            .chain(self.context.iter().map(|(line_number, line)| (*line_number, None, line.to_owned())))
+            .collect::<Vec<_>>();
+        lines.sort_by_key(|(line_number, _, _)| *line_number);
+        let indent = " ".repeat(self.lines.len().to_string().len() + 2);
+        let mut previous = None;
+        for (line_number, index, line) in lines {
+            if !self.context.is_empty() && previous.is_some_and(|previous| previous + 1 < line_number) {
+                s += "   --\n";
+            }
+            previous = Some(line_number);
+            match index {
+                Some(index) => {
+                    // [12-15] for a match over several lines
+                    let end_line_number = self.lines[index].end_line_number;
+                    let range = match end_line_number == line_number {
+                        true => line_number.to_string(),
+                        false => format!("{}-{}", line_number, end_line_number),
+                    };
+                    previous = Some(end_line_number);
+                    s += format!("   {}{}{}{}{}) [{}] : {}\n", paint(&color::Fg(color::Yellow)),
+                                                                       paint(&style::Bold), 
+                                                                       index + 1, 
+                                                                       paint(&style::Reset), 
+                                                                       paint(&color::Fg(color::Reset)),
+                                                                       range, 
+                                                                       line
+                                                                       ).as_str()
+                }
+                None => s += format!("   {}{}[{}] - {}{}\n", indent, paint(&style::Faint), line_number, line, paint(&style::Reset)).as_str(),
+            }
+        }
+        s
+    }
 }
 
 impl fmt::Display for SearchResult  {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut s = format!("{}{}{}{}{}\n", color::Fg(color::Green), 
-                                                    style::Bold, 
-                                                    self.file_name, 
-                                                    style::Reset, 
-                                                    color::Fg(color::Reset)
-                                                    );
-        for (index, item)  in self.lines.iter().enumerate() {
-            s += format!("   {}{}{}{}{}) [{}] : {}\n", color::Fg(color::Yellow),
-                                                       style::Bold, 
-                                                       index + 1, 
-                                                       style::Reset, 
-                                                       color::Fg(color::Reset),
-                                                       item.0, 
-                                                       item.1
-                                                       ).as_str()
-        }
-        write!(f, "{}", s)
+        write!(f, "{}", self.to_text(true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "a\nb\nc\nd\ne\nf\ng\nh\n";
+
+    fn found(line_number: u32) -> SearchLine {
+        let line = CONTENT.lines().nth(line_number as usize - 1).unwrap();
+        SearchLine::new(line_number, line, 0, &[Range { start: 0, end: line.len() }])
+    }
+
+    fn numbers(context: &[(u32, String)]) -> Vec<u32> {
+        context.iter().map(|(line_number, _)| *line_number).collect()
+    }
+
+    #[test]
+    fn columns_count_characters_from_the_untrimmed_line() {
+        let line = "\t  let café = foo(foo);";
+        let start = line.find("foo").unwrap();
+        let found = SearchLine::new(3, line, start, &[start..start + 3, start + 4..start + 7]);
+        // a tab and two spaces, then 11 characters for 12 bytes
+        assert_eq!(found.column, 15);
+        assert_eq!(found.text, "let café = foo(foo);");
+        assert_eq!(found.spans, [12..15, 16..19]);
+        assert_eq!(&found.text[found.spans[0].clone()], "foo");
+    }
+
+    #[test]
+    fn spans_are_clamped_to_the_trimmed_text() {
+        // a match on the spaces around the text, or going past the end of the line
+        let line = "  foo  ";
+        let found = SearchLine::new(1, line, 0, &[0..2, 2..9]);
+        assert_eq!(found.column, 1);
+        assert_eq!(found.spans.len(), 1);
+        assert_eq!(found.spans[0], 0..3);
+    }
+
+    #[test]
+    fn a_match_may_start_on_the_line_break() {
+        // "\r\nfoo" in "let foo = 1;\r\nfoo", the \r being trimmed from the line
+        let found = SearchLine::new(1, "let foo = 1;", 12, &[Range { start: 12, end: 17 }]);
+        assert_eq!(found.column, 13);
+        assert!(found.spans.is_empty());
+    }
+
+    #[test]
+    fn multiline_matches_show_their_range_of_lines() {
+        let mut result = SearchResult::new("main.rs".to_string());
+        let mut found = SearchLine::new(2, "unsafe {", 0, &[Range { start: 0, end: 12 }]);
+        found.add_end_line_number(4);
+        result.add_context(context_lines(CONTENT, &[found.clone()], (1, 1)));
+        result.add_lines(found);
+        result.add_lines(SearchLine::new(7, "unsafe {}", 0, &[Range { start: 0, end: 8 }]));
+        assert_eq!(result.to_text(false), "main.rs\n      [1] - a\n   1) [2-4] : unsafe {\n      [5] - e\n   --\n   2) [7] : unsafe {}\n");
+    }
+
+    #[test]
+    fn matches_are_highlighted() {
+        let found = SearchLine::new(1, "a foo b foo", 2, &[2..5, 8..11]);
+        let (red, reset) = (format!("{}{}", color::Fg(color::Red), style::Bold), format!("{}{}", style::Reset, color::Fg(color::Reset)));
+        assert_eq!(found.highlighted(true), format!("a {}foo{} b {}foo{}", red, reset, red, reset));
+        assert_eq!(found.highlighted(false), "a foo b foo");
+    }
+
+    #[test]
+    fn context_lines_merge_the_overlapping_windows() {
+        let context = context_lines(CONTENT, &[found(2), found(4)], (1, 1));
+        assert_eq!(numbers(&context), [1, 3, 5]);
+        assert_eq!(context[1], (3, "c".to_string()));
+        assert!(context_lines(CONTENT, &[found(2), found(4)], (0, 0)).is_empty());
+    }
+
+    #[test]
+    fn context_lines_stop_at_the_start_and_the_end_of_the_file() {
+        assert_eq!(numbers(&context_lines(CONTENT, &[found(1)], (3, 1))), [2]);
+        assert_eq!(numbers(&context_lines(CONTENT, &[found(8)], (1, 3))), [7]);
+        assert_eq!(numbers(&context_lines(CONTENT, &[found(2), found(7)], (2, 0))), [1, 5, 6]);
+    }
+
+    #[test]
+    fn context_is_shown_with_separators_between_the_groups() {
+        let mut result = SearchResult::new("main.rs".to_string());
+        let lines = [found(2), found(7)];
+        result.add_context(context_lines(CONTENT, &lines, (1, 0)));
+        for line in lines {
+            result.add_lines(line);
+        }
+        assert_eq!(result.to_text(false), "main.rs\n      [1] - a\n   1) [2] : b\n   --\n      [6] - f\n   2) [7] : g\n");
+        // no separator without context, even between lines far apart
+        result.add_context(Vec::new());
+        assert_eq!(result.to_text(false), "main.rs\n   1) [2] : b\n   2) [7] : g\n");
+        assert_eq!(result.to_string(), result.to_text(true));
     }
 }
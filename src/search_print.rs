@@ -13,16 +13,12 @@ use termion::{
 
 use crate::search::SearchResult;
 
-pub fn print_and_choose( list: &Vec<SearchResult>) {
+pub fn print_and_choose(list: &[SearchResult]) {
     let mut file_selected = false;
     let mut line_selected = false;
     let mut file_choosen = 0;
     let mut line_choosen = 0;
-    let mut total_pattern_found = 0;
-    
-    for i in 0..list.len() {
-        total_pattern_found += list[i].lines.len();
-    }
+    let total_pattern_found: usize = list.iter().map(|item| item.lines.len()).sum();
     
     loop { // TODO display the number of times the pattern is found
         print!("{}[2J", 27 as char);
@@ -34,7 +30,7 @@ pub fn print_and_choose( list: &Vec<SearchResult>) {
             println!("Press {}{}enter{}{} to select a file", color::Fg(color::Red), style::Bold, style::Reset, color::Fg(color::Reset));
         }
         println!("Number of time (// TODO insert the pattern here) was found : {}{}{}{}{}", color::Fg(color::Green), style::Bold, total_pattern_found, style::Reset, color::Fg(color::Reset));
-        println!("");
+        println!();
         for (index, item) in list.iter().enumerate() {
             if file_choosen != 0 {
                 let index_string = format!("{} ", index + 1);
@@ -86,14 +82,14 @@ pub fn print_and_choose( list: &Vec<SearchResult>) {
                                     return;
                                 }
                             };
-                            let line = item.lines[line_choosen - 1].0; // TODO add a way to give the line where the pattern in found 
+                            let found = &item.lines[line_choosen - 1]; // TODO add a way to give the line where the pattern in found 
                                                                             // ex : file blaba
                                                                             //     1) [42] pattern
                                                                             // possibility to give 42
                                                                             // + error can append if line choosen not in lines
                             Command::new("/usr/bin/sh")
                                                 .arg("-c")
-                                                .arg(format!("{} +{} {}",editor, line, item.file_name))
+                                                .arg(editor_command(&editor, &item.file_name, found.line_number, found.column))
                                                 .spawn()
                                                 .expect("Error: Failed to run editor")
                                                 .wait()
@@ -105,40 +101,36 @@ pub fn print_and_choose( list: &Vec<SearchResult>) {
                     }
                 }
             }
-            termion::event::Key::Backspace | termion::event::Key::Delete=> {
-                if file_choosen != 0 {
-                    if line_choosen != 0 && file_selected {
-                        if line_choosen.to_string().len() == 1 {
-                            line_choosen = 0;
-                        } else {
-                            line_choosen = line_choosen / 10;
-                        }
-                        line_selected = false;
+            termion::event::Key::Backspace | termion::event::Key::Delete if file_choosen != 0 => {
+                if line_choosen != 0 && file_selected {
+                    if line_choosen.to_string().len() == 1 {
+                        line_choosen = 0;
                     } else {
-                        if file_choosen.to_string().len() == 1 {
-                            file_choosen = 0;
-                        } else {
-                            file_choosen = file_choosen / 10;
-                        }
-                        file_selected = false;
+                        line_choosen /= 10;
+                    }
+                    line_selected = false;
+                } else {
+                    if file_choosen.to_string().len() == 1 {
+                        file_choosen = 0;
+                    } else {
+                        file_choosen /= 10;
                     }
+                    file_selected = false;
                 }
             }
-            termion::event::Key::Char(c) => {
-                if c.is_digit(10) {
-                    if file_choosen != 0 {
-                        if file_selected {
-                            if line_choosen != 0 {
-                                line_choosen = line_choosen * 10 + c.to_digit(10).unwrap() as usize;
-                            } else {
-                                line_choosen = c.to_digit(10).unwrap() as usize;
-                            }
+            termion::event::Key::Char(c) if c.is_ascii_digit() => {
+                if file_choosen != 0 {
+                    if file_selected {
+                        if line_choosen != 0 {
+                            line_choosen = line_choosen * 10 + c.to_digit(10).unwrap() as usize;
                         } else {
-                            file_choosen = file_choosen * 10 + c.to_digit(10).unwrap() as usize;
+                            line_choosen = c.to_digit(10).unwrap() as usize;
                         }
                     } else {
-                        file_choosen = c.to_digit(10).unwrap() as usize;
+                        file_choosen = file_choosen * 10 + c.to_digit(10).unwrap() as usize;
                     }
+                } else {
+                    file_choosen = c.to_digit(10).unwrap() as usize;
                 }
             }
             _ => {
@@ -146,3 +138,53 @@ pub fn print_and_choose( list: &Vec<SearchResult>) {
         }
     }
 }
+
+// Without a terminal, when the output is piped or redirected: the list is
+// printed once, without colors, and nothing is asked
+pub fn print_results(list: &[SearchResult]) {
+    for (index, item) in list.iter().enumerate() {
+        print!("{}) {}", index + 1, item.to_text(false));
+    }
+}
+
+// Opens the file at the line and the column of the match, for the editors we
+// know how to give a column to, and only at the line for the others
+fn editor_command(editor: &str, file_name: &str, line: u32, column: usize) -> String {
+    let program = editor.split_whitespace().next().unwrap_or_default();
+    let file_name = format!("'{}'", file_name.replace('\'', r"'\''"));
+    match program.rsplit('/').next().unwrap_or_default() {
+        "vim" | "nvim" | "gvim" => format!("{} '+call setcursorcharpos({}, {})' {}", editor, line, column, file_name),
+        "emacs" | "emacsclient" | "micro" | "kak" => format!("{} +{}:{} {}", editor, line, column, file_name),
+        "nano" => format!("{} +{},{} {}", editor, line, column, file_name),
+        "code" | "codium" => format!("{} --goto {}:{}:{}", editor, file_name, line, column),
+        "subl" | "hx" | "zed" => format!("{} {}:{}:{}", editor, file_name, line, column),
+        _ => format!("{} +{} {}", editor, line, file_name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_editor_is_given_the_line_and_the_column() {
+        let commands = [
+            ("vim", "vim '+call setcursorcharpos(12, 5)' 'src/main.rs'"),
+            ("/usr/bin/nvim", "/usr/bin/nvim '+call setcursorcharpos(12, 5)' 'src/main.rs'"),
+            ("emacsclient -t", "emacsclient -t +12:5 'src/main.rs'"),
+            ("kak", "kak +12:5 'src/main.rs'"),
+            ("nano", "nano +12,5 'src/main.rs'"),
+            ("code --wait", "code --wait --goto 'src/main.rs':12:5"),
+            ("hx", "hx 'src/main.rs':12:5"),
+            ("ed", "ed +12 'src/main.rs'"),
+        ];
+        for (editor, command) in commands {
+            assert_eq!(editor_command(editor, "src/main.rs", 12, 5), command);
+        }
+    }
+
+    #[test]
+    fn file_names_are_quoted_for_the_shell() {
+        assert_eq!(editor_command("micro", "it's $HOME.rs", 1, 1), r"micro +1:1 'it'\''s $HOME.rs'");
+    }
+}
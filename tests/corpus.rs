@@ -0,0 +1,388 @@
+// Every file of tests/corpus has the expected counts of its lines on its first
+// line (the second one after a shebang), like "// mop: lines 12 code 6 comment 3
+// docs 1 blank 2", checked against the per-file json output of the metric subcommand.
+// The counts of the languages embedded in the file follow, like "; CSS: lines 4 code 4".
+// The header is in ASCII, whatever the encoding of the file (UTF-16 with a BOM, Latin-1...)
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use regex::Regex;
+use serde_json::Value;
+
+const CORPUS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus");
+
+// column -> number of lines
+type Counts = Vec<(String, u64)>;
+
+// Embedded language (None for the file itself) -> expected counts
+fn expected_counts(path: &Path) -> Vec<(Option<String>, Counts)> {
+    let bytes = fs::read(path).unwrap();
+    let (content, _, _) = encoding_rs::UTF_8.decode(&bytes);
+    let header = Regex::new(r"mop:((?:;?(?:\s*[\w+#. ]+:)?(?:\s+[a-z]+ \d+)+)+)").unwrap();
+    let segment = Regex::new(r"^\s*(?:([\w+#. ]+):)?((?:\s+[a-z]+ \d+)+)$").unwrap();
+    let counts = match content.lines().take(2).find_map(|line| header.captures(line)) {
+        Some(captures) => captures.get(1).unwrap().as_str().to_string(),
+        None => panic!("{} has no \"mop:\" header", path.display()),
+    };
+
+    counts.split(';').map(|counts| {
+        let captures = segment.captures(counts).unwrap();
+        let words = captures.get(2).unwrap().as_str().split_whitespace().collect::<Vec<_>>();
+        let counts = words.chunks(2).map(|pair| (pair[0].to_string(), pair[1].parse().unwrap())).collect();
+        (captures.get(1).map(|language| language.as_str().trim().to_string()), counts)
+    }).collect()
+}
+
+// A directory of the system temporary directory, removed with its files when dropped
+struct TempDir(PathBuf);
+
+impl TempDir {
+    // name is unique to each test, the tests running in parallel
+    fn new(name: &str, files: &[(&str, &[u8])]) -> TempDir {
+        let directory = TempDir(std::env::temp_dir().join(format!("mop-{}-{}", name, std::process::id())));
+        for (path, content) in files {
+            let path = directory.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        directory
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// The metric subcommand run on path, with the languages of the user read from
+// config/mop/languages.toml. CORPUS has none, to keep the ones of the user out of the way
+fn run_metric(config: &Path, path: &Path, options: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mop"))
+        .arg("metric")
+        .args(options)
+        .arg(path)
+        .env("XDG_CONFIG_HOME", config)
+        .output()
+        .unwrap()
+}
+
+// The json output of the metric subcommand, which must succeed
+fn metric(config: &Path, path: &Path, options: &[&str]) -> Value {
+    let output = run_metric(config, path, &[&["--format", "json"], options].concat());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+// Per-file json output for the files of the corpus
+fn corpus_metric(path: &Path, options: &[&str]) -> Value {
+    metric(Path::new(CORPUS), path, &[&["--no-ignore", "--by-file"], options].concat())
+}
+
+#[test]
+fn corpus_line_counts() {
+    let json = corpus_metric(Path::new(CORPUS), &[]);
+    let files = json["files"].as_array().unwrap();
+    assert!(!files.is_empty());
+
+    let mut failures = Vec::new();
+    for file in files {
+        let path = Path::new(file["path"].as_str().unwrap());
+        let embedded = file.get("embedded_in").map(|_| file["language"].as_str().unwrap().to_string());
+        let expected = expected_counts(path);
+        let counts = match expected.iter().find(|(language, _)| *language == embedded) {
+            Some((_, counts)) => counts,
+            None => {
+                failures.push(format!("{}: unexpected {} code", path.display(), file["language"]));
+                continue;
+            }
+        };
+        for (column, expected) in counts {
+            let actual = match file.get(column).and_then(Value::as_u64) {
+                Some(actual) => actual,
+                None => panic!("{}: unknown column {}", path.display(), column),
+            };
+            if actual != *expected {
+                failures.push(format!("{} ({}): {} {}, expected {}", path.display(), file["language"], column, actual, expected));
+            }
+        }
+    }
+    for path in fs::read_dir(CORPUS).unwrap().map(|entry| entry.unwrap().path()).filter(|path| path.is_file()) {
+        for (language, _) in expected_counts(&path).iter().filter(|(language, _)| language.is_some()) {
+            let found = files.iter().any(|file| Path::new(file["path"].as_str().unwrap()) == path && file["language"] == language.as_deref().unwrap());
+            if !found {
+                failures.push(format!("{}: no {} code found", path.display(), language.as_deref().unwrap()));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn mixed_lines_policies() {
+    let path = Path::new(CORPUS).join("strings.c");
+    for (policy, code, comment) in [("code", 7, 5), ("comment", 5, 7), ("both", 7, 7)] {
+        let json = corpus_metric(&path, &["--mixed", policy]);
+        let file = &json["files"][0];
+        assert_eq!((file["code"].as_u64(), file["comment"].as_u64()), (Some(code), Some(comment)), "--mixed {}", policy);
+        assert_eq!(file["mixed"].as_u64(), Some(2), "--mixed {}", policy);
+    }
+}
+
+#[test]
+fn binary_and_undecodable_files_are_skipped() {
+    let late = [b"int x;\n".repeat(2000), vec![0]].concat();
+    let directory = TempDir::new("skipped", &[
+        // a UTF-8 BOM followed by Latin-1, and a Latin-1 file with control characters
+        ("bom.c", b"\xef\xbb\xbfint caf\xe9;\n"),
+        ("control.c", b"int caf\xe9;\x01\x02\n"),
+        ("ok.c", b"int caf\xe9;\n"),
+        // a gzip stream without NUL bytes, and a NUL byte far from the beginning
+        ("data.c", b"\x1f\x8b\x08\x08int x;\n"),
+        ("late.c", &late),
+    ]);
+
+    let json = corpus_metric(&directory.0, &[]);
+    assert_eq!(json["totals"]["files"].as_u64(), Some(1));
+    assert_eq!(json["totals"]["ignored_files"].as_u64(), Some(2));
+    assert_eq!(json["totals"]["undecodable_files"].as_u64(), Some(2));
+}
+
+#[test]
+fn unreadable_paths_are_reported() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let directory = TempDir::new("errors", &[("ok.c", b"int x;\n")]);
+    fs::write(directory.0.join(std::ffi::OsStr::from_bytes(b"bad\xff.c")), "int y;\n").unwrap();
+
+    let lenient = run_metric(Path::new(CORPUS), &directory.0, &["--format", "json"]);
+    let strict = run_metric(Path::new(CORPUS), &directory.0, &["--format", "json", "--strict"]);
+
+    assert_eq!(lenient.status.code(), Some(0));
+    let json: Value = serde_json::from_slice(&lenient.stdout).unwrap();
+    assert_eq!(json["totals"]["files"].as_u64(), Some(1));
+    assert!(String::from_utf8_lossy(&lenient.stderr).contains("1 path skipped because of errors"));
+    assert_eq!(strict.status.code(), Some(2));
+    assert!(strict.stdout.is_empty());
+}
+
+#[test]
+fn by_dir_depth_needs_an_equal_sign() {
+    // tree, tree/a, tree/a/b and tree/c
+    let directories = |option: &str| {
+        let json = metric(Path::new(CORPUS), &Path::new(CORPUS).join("tree"), &["--no-ignore", option]);
+        json["directories"].as_array().unwrap().len()
+    };
+    // the path given right after --by-dir is not taken for its depth
+    assert_eq!(directories("--by-dir"), 4);
+    assert_eq!(directories("--by-dir=1"), 3);
+}
+
+#[test]
+fn shared_extensions_are_disambiguated_by_content() {
+    let directory = TempDir::new("heuristics", &[
+        ("vector.h", b"namespace geometry {\nstruct Vector;\n}\n"),
+        ("point.h", b"struct point { int x, y; };\n"),
+        ("view.h", b"#import <Foundation/Foundation.h>\n@interface View\n@end\n"),
+        ("norm.m", b"function n = norm(v)\n  n = sqrt(sum(v .^ 2));\nend\n"),
+        ("facts.pl", b"parent(tom, bob).\nancestor(X, Y) :- parent(X, Y).\n"),
+        ("script.pl", b"use strict;\nmy $x = 1;\n"),
+    ]);
+    let json = metric(Path::new(CORPUS), &directory.0, &["--by-file", "--sort", "name"]);
+    let languages = json["files"].as_array().unwrap().iter().map(|file| file["language"].as_str().unwrap()).collect::<Vec<_>>();
+    assert_eq!(languages, ["Prolog", "MATLAB", "C", "Perl", "C++", "Objective-C"]);
+}
+
+#[test]
+fn user_languages_win_over_the_heuristics() {
+    let directory = TempDir::new("user-languages", &[
+        ("mop/languages.toml", b"[MyH]\nextensions = [\"h\"]\nline_comment = [\"#\"]\n"),
+        ("header.h", b"# comment\nint main(void);\n"),
+    ]);
+    let json = metric(&directory.0, &directory.0.join("header.h"), &["--by-file"]);
+    assert_eq!(json["files"][0]["language"], "MyH");
+    assert_eq!(json["files"][0]["comment"], 1);
+}
+
+#[test]
+fn piped_tables_have_no_progress_line() {
+    let tree = Path::new(CORPUS).join("tree");
+    for options in [&["--no-ignore"][..], &["--no-ignore", "--by-file"], &["--no-ignore", "--by-dir"]] {
+        let output = run_metric(Path::new(CORPUS), &tree, options);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("| Code lines |"), "{:?}", stdout);
+        assert!(!stdout.contains("Number of files :") && !stdout.contains(['\x1B', '\r']), "{:?}", stdout);
+    }
+}
+
+#[test]
+fn top_lists_the_first_files_and_totals_all_of_them() {
+    let tree = Path::new(CORPUS).join("tree");
+    let json = metric(Path::new(CORPUS), &tree, &["--no-ignore", "--by-file", "--sort", "size", "--top", "2"]);
+    let paths = json["files"].as_array().unwrap().iter().map(|file| file["path"].as_str().unwrap()).collect::<Vec<_>>();
+    // deep.rs and side.rs are the biggest, with the same size, the tie is broken by path
+    assert_eq!(paths, [tree.join("a/b/deep.rs").to_str().unwrap(), tree.join("c/side.rs").to_str().unwrap()]);
+    assert_eq!(json["totals"]["files"], 4);
+
+    let table = |options: &[&str]| String::from_utf8(run_metric(Path::new(CORPUS), &tree, options).stdout).unwrap();
+    assert!(table(&["--no-ignore", "--by-file", "--top", "2"]).contains("| Total (all 4 files) "));
+    assert!(table(&["--no-ignore", "--by-file", "--top", "4"]).contains("| Total "));
+    assert!(!table(&["--no-ignore", "--by-file", "--top", "4"]).contains("all 4 files"));
+}
+
+#[test]
+fn by_dir_sorts_the_subdirectories() {
+    let tree = Path::new(CORPUS).join("tree");
+    let directories = |options: &[&str]| {
+        let json = metric(Path::new(CORPUS), &tree, &[&["--no-ignore", "--by-dir"], options].concat());
+        json["directories"].as_array().unwrap().iter()
+            .map(|directory| directory["path"].as_str().unwrap().strip_prefix(CORPUS).unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    // a has more lines than c, and its subdirectories are listed right after it
+    assert_eq!(directories(&[]), ["/tree", "/tree/a", "/tree/a/b", "/tree/c"]);
+    assert_eq!(directories(&["--reverse"]), ["/tree", "/tree/c", "/tree/a", "/tree/a/b"]);
+    // no blank lines anywhere, the ties are broken by name
+    assert_eq!(directories(&["--sort", "blank"]), ["/tree", "/tree/a", "/tree/a/b", "/tree/c"]);
+    assert_eq!(directories(&["--sort", "name", "--reverse"]), ["/tree", "/tree/c", "/tree/a", "/tree/a/b"]);
+}
+
+// The paths of the files of the per-file json output, relative to directory
+fn listed_files(json: &Value, directory: &Path) -> Vec<String> {
+    let mut files = json["files"].as_array().unwrap().iter()
+        .map(|file| Path::new(file["path"].as_str().unwrap()).strip_prefix(directory).unwrap().to_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+#[test]
+fn ignore_files_are_respected() {
+    let directory = TempDir::new("ignore-files", &[
+        // .gitignore is only read in a git repository
+        (".git/HEAD", b"ref: refs/heads/main\n"),
+        (".gitignore", b"generated.rs\n"),
+        (".ignore", b"skipped.rs\n"),
+        (".mopignore", b"vendor/\n"),
+        ("main.rs", b"fn main() {}\n"),
+        ("generated.rs", b"fn generated() {}\n"),
+        ("skipped.rs", b"fn skipped() {}\n"),
+        ("vendor/lib.rs", b"fn lib() {}\n"),
+    ]);
+    let files = |options: &[&str]| listed_files(&metric(Path::new(CORPUS), &directory.0, &[&["--by-file"], options].concat()), &directory.0);
+    assert_eq!(files(&[]), ["main.rs"]);
+    assert_eq!(files(&["--no-ignore"]), ["generated.rs", "main.rs", "skipped.rs", "vendor/lib.rs"]);
+}
+
+#[test]
+fn include_exclude_and_lang_filters() {
+    let directory = TempDir::new("filters", &[
+        ("src/main.rs", b"fn main() {}\n"),
+        ("src/lib.py", b"x = 1\n"),
+        ("tests/test.rs", b"fn test() {}\n"),
+        ("run", b"#!/usr/bin/env python3\nprint(1)\n"),
+    ]);
+    let files = |options: &[&str]| listed_files(&metric(Path::new(CORPUS), &directory.0, &[&["--by-file"], options].concat()), &directory.0);
+    // a glob matches the file name or the path relative to the directory given
+    assert_eq!(files(&["--include", "*.rs"]), ["src/main.rs", "tests/test.rs"]);
+    assert_eq!(files(&["--include", "src/**"]), ["src/lib.py", "src/main.rs"]);
+    assert_eq!(files(&["--exclude", "src/**", "--exclude", "*.rs"]), ["run"]);
+    assert_eq!(files(&["--lang", "Python"]), ["run", "src/lib.py"]);
+    assert_eq!(files(&["--lang", "Rust,Python", "--exclude", "tests/**", "--include", "*.rs"]), ["src/main.rs"]);
+    // nothing left to count
+    assert_eq!(run_metric(Path::new(CORPUS), &directory.0, &["--lang", "Go"]).status.code(), Some(1));
+}
+
+// The keys of a json object, sorted
+fn keys(object: &Value) -> Vec<&str> {
+    let mut keys = object.as_object().unwrap().keys().map(String::as_str).collect::<Vec<_>>();
+    keys.sort_unstable();
+    keys
+}
+
+#[test]
+fn json_schema() {
+    let tree = Path::new(CORPUS).join("tree");
+    let json = metric(Path::new(CORPUS), &tree, &["--no-ignore"]);
+    assert_eq!(keys(&json), ["languages", "totals", "version"]);
+    assert_eq!(json["version"], 1);
+    assert_eq!(json["totals"], serde_json::json!({
+        "files": 4, "ignored_files": 0, "undecodable_files": 0, "size": 214,
+        "blank": 0, "comment": 4, "docs": 0, "code": 4, "mixed": 0, "lines": 8,
+    }));
+    assert_eq!(json["languages"], serde_json::json!([
+        { "language": "Rust", "files": 4, "size": 214, "blank": 0, "comment": 4, "docs": 0, "code": 4, "mixed": 0, "lines": 8 },
+    ]));
+
+    let json = metric(Path::new(CORPUS), &tree, &["--no-ignore", "--by-file"]);
+    assert_eq!(keys(&json), ["files", "languages", "totals", "version"]);
+    assert_eq!(keys(&json["files"][0]), ["blank", "code", "comment", "docs", "language", "lines", "mixed", "path", "size"]);
+    let json = metric(Path::new(CORPUS), &tree, &["--no-ignore", "--by-dir"]);
+    assert_eq!(keys(&json), ["directories", "languages", "totals", "version"]);
+    assert_eq!(keys(&json["directories"][0]), ["blank", "code", "comment", "docs", "files", "lines", "mixed", "path", "size"]);
+}
+
+#[test]
+fn csv_and_markdown_tables() {
+    let directory = TempDir::new("tables", &[("a,b.rs", b"// a\nfn a() {}\n"), ("x|y.py", b"x = 1\n\n")]);
+    let output = |options: &[&str]| {
+        let output = run_metric(Path::new(CORPUS), &directory.0, options);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap().replace(directory.0.to_str().unwrap(), "DIR")
+    };
+    assert_eq!(output(&["--format", "csv"]), "language,files,size,blank,comment,docs,code,total\n\
+        Python,1,7,1,0,0,1,2\nRust,1,15,0,1,0,1,2\nTotal,2,22,1,1,0,2,4\n");
+    // the separators in the paths are quoted or escaped
+    assert_eq!(output(&["--format", "csv", "--by-file", "--sort", "name"]), "path,language,size,blank,comment,docs,code,total\n\
+        \"DIR/a,b.rs\",Rust,15,0,1,0,1,2\nDIR/x|y.py,Python,7,1,0,0,1,2\n");
+    assert_eq!(output(&["--format", "markdown", "--by-file", "--sort", "name"]), "| Path | Language | Size | Blank | Comment | Docs | Code | Total |\n\
+        |:-----|:---------|-----:|------:|--------:|-----:|-----:|------:|\n\
+        | DIR/a,b.rs | Rust | 15 | 0 | 1 | 0 | 1 | 2 |\n| DIR/x\\|y.py | Python | 7 | 1 | 0 | 0 | 1 | 2 |\n");
+    assert_eq!(output(&["--format", "markdown"]), "| Language | Files | Size | Blank | Comment | Docs | Code | Total |\n\
+        |:---------|------:|-----:|------:|--------:|-----:|-----:|------:|\n\
+        | Python | 1 | 7 | 1 | 0 | 0 | 1 | 2 |\n| Rust | 1 | 15 | 0 | 1 | 0 | 1 | 2 |\n\
+        | **Total** | **2** | **22** | **1** | **1** | **0** | **2** | **4** |\n");
+}
+
+#[test]
+fn languages_are_sorted_with_ties_broken_by_name() {
+    let directory = TempDir::new("sort", &[
+        ("main.rs", b"fn main() {}\n\n"),
+        ("main.py", b"# main\nmain()\n"),
+        ("main.go", b"package main\n\n"),
+        ("lib.go", b"package main\n"),
+    ]);
+    let languages = |options: &[&str]| {
+        let json = metric(Path::new(CORPUS), &directory.0, options);
+        json["languages"].as_array().unwrap().iter().map(|language| language["language"].as_str().unwrap().to_string()).collect::<Vec<_>>()
+    };
+    assert_eq!(languages(&[]), ["Go", "Python", "Rust"]);
+    // Python and Rust have the same number of lines, the tie is broken by name, then reversed
+    assert_eq!(languages(&["--reverse"]), ["Rust", "Python", "Go"]);
+    assert_eq!(languages(&["--sort", "blank"]), ["Go", "Rust", "Python"]);
+    assert_eq!(languages(&["--sort", "comment"]), ["Python", "Go", "Rust"]);
+    assert_eq!(languages(&["--sort", "name", "--reverse"]), ["Rust", "Python", "Go"]);
+}
+
+#[test]
+fn user_languages_file() {
+    let directory = TempDir::new("user-file", &[
+        ("mop/languages.toml", b"[MyDsl]\nextensions = [\"dsl\"]\nfilenames = [\"Dslfile\"]\nline_comment = [\"#\"]\n\
+            block_comment = [[\"{-\", \"-}\"]]\nnested_comments = true\n"),
+        ("src/rules.dsl", b"# rules\n{- {- nested -} still a comment -}\nrule = 1\n"),
+        ("src/Dslfile", b"rule = 2\n"),
+    ]);
+    let json = metric(&directory.0, &directory.0.join("src"), &["--by-file", "--lang", "mydsl", "--sort", "name"]);
+    let files = json["files"].as_array().unwrap();
+    assert_eq!(files.iter().map(|file| file["language"].as_str().unwrap()).collect::<Vec<_>>(), ["MyDsl", "MyDsl"]);
+    assert_eq!((&files[1]["comment"], &files[1]["code"]), (&Value::from(2), &Value::from(1)));
+
+    // an invalid file is reported and the bundled languages are still there
+    fs::write(directory.0.join("mop/languages.toml"), "[MyDsl]\nextension = [\"dsl\"]\n").unwrap();
+    let output = run_metric(&directory.0, &Path::new(CORPUS).join("tree"), &["--format", "json"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("mop/languages.toml is ignored"), "{}", String::from_utf8_lossy(&output.stderr));
+}
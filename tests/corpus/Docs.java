@@ -0,0 +1,17 @@
+// mop: lines 17 code 5 comment 6 docs 5 blank 1
+/**
+ * A documented class.
+ */
+public class Docs {
+    /** The answer. */
+    static int answer = 42;
+
+    /*****************
+     * a banner comment
+     *****************/
+    /***/
+    /* not documentation */
+    public static void main(String[] args) {
+        /** documentation inside a method, still counted */
+    }
+}
@@ -0,0 +1,11 @@
+-- mop: lines 11 code 4 comment 6 blank 1
+local s = "--[[ not a comment"
+--[[ a long comment
+print("commented out")
+]]
+local t = [[
+-- inside a long string
+]]
+
+-- the end
+--]] a line comment, not the end of a block
@@ -0,0 +1,21 @@
+//! mop: lines 21 code 5 comment 6 docs 7 blank 3
+//! Crate documentation
+
+/// Documented function
+/// on two lines
+fn documented() {}
+
+/**
+ * Block documentation
+ */
+fn block_documented() {}
+
+////////////////////////////
+//// a banner, not documentation
+/*** a banner either ***/
+/* a regular comment */
+/**/ // an empty comment followed by a comment
+// a regular comment
+fn main() {
+    let s = "/// not documentation"; /** trailing doc */
+}
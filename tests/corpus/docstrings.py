@@ -0,0 +1,15 @@
+# mop: lines 15 code 7 comment 2 docs 3 blank 3
+def greet(name):
+    """Say hello.
+
+    # not a comment, still the docstring
+    """
+    message = "# not a comment either"
+    print(message)  # a comment after code
+
+    # a comment
+    return '''
+# inside a string
+'''
+
+greet("world")
@@ -0,0 +1,5 @@
+/* mop: lines 5 code 2 comment 3 blank 0 */
+/* C comments don't nest: /* is just text
+ */
+int x; /* the first */ closes it, so this line is code */
+int y;
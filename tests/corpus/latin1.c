@@ -0,0 +1,6 @@
+/* mop: lines 6 code 3 comment 2 blank 1 */
+/* Modifi par Ren, encod en Latin-1 */
+
+const char *salut = "a va, Zo ?";
+int anne = 2; // 
+int x;
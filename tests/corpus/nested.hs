@@ -0,0 +1,8 @@
+-- mop: lines 8 code 3 comment 4 blank 1
+{- outer {- inner -}
+   still outer -}
+main :: IO ()
+main = putStrLn "{- not a comment"
+
+{- {- -} -}
+x = 1 {- trailing {- nested -} -}
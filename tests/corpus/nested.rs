@@ -0,0 +1,13 @@
+// mop: lines 13 code 4 comment 8 blank 1
+/* a comment with /* a nested one */
+   still in the outer comment */
+fn main() {
+    /*
+    let commented_out = 1; /* with its own comment */
+    /* and another
+       on two lines */
+    */
+    let x = "*/ /*"; // a string, not a comment
+}
+
+/* /* */ */ fn after_nested() {}
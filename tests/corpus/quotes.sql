@@ -0,0 +1,8 @@
+-- mop: lines 8 code 4 comment 3 blank 1
+SELECT 'C:\' AS path, '--' AS dashes; -- a trailing comment
+/* a block
+comment */
+
+SELECT 'it''s' AS text,
+  '/*' AS opener
+FROM dual;
@@ -0,0 +1,10 @@
+// mop: lines 10 code 7 comment 2 blank 1
+package main
+
+const raw = `a raw string
+// spanning lines
+with a \ backslash`
+/* a comment */
+func main() {
+	_ = raw + "*/"
+}
@@ -0,0 +1,9 @@
+#!/bin/sh
+# mop: lines 9 code 5 comment 3 blank 1
+echo "# not a comment"
+echo '# not a comment, and no \ escape'
+# a comment
+
+long="a string
+# spanning lines"
+echo "$long" # a comment after code
@@ -0,0 +1,14 @@
+/* mop: lines 14 code 7 comment 5 blank 2 mixed 2 */
+#include <stdio.h>
+
+int main(void) {
+    char *s = "/* not a comment";
+    char c = '"'; /* a comment after a char */
+    // printf("commented out");
+    printf("%s %c\n", s, c); // trailing comment
+    /* one */ /* two */
+
+    /* the end
+       of the file */
+    return 0;
+}
@@ -0,0 +1,24 @@
+// mop: lines 24 code 15 comment 5 blank 4 mixed 3
+fn main() {
+    let open = "/*"; // not the start of a comment
+    let close = "*/";
+    let x = 1; /* trailing block comment */
+
+    /* a block comment opened and closed on its line */
+    let quote = '"';
+    let escaped = "a \" /* still a string";
+    let raw = r#"a "raw" string with /* and // inside"#;
+
+    let multi = "a string
+/* spanning */
+lines";
+    /*
+     * a real block comment
+     */
+}
+
+fn longest<'a>(a: &'a str, b: &'a str) -> &'a str {
+    if a.len() > b.len() { a } else { b }
+}
+
+/* comment */ fn after_comment() {}
@@ -0,0 +1,12 @@
+// mop: lines 12 code 7 comment 3 blank 2
+const url = "http://example.com"; // a comment after a string with //
+const html = `
+  // inside a template literal
+  /* still the template */
+`;
+
+/* a block comment
+*/
+const s = 'it\'s /* a string */';
+
+console.log(url, html, s);
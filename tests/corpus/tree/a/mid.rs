@@ -0,0 +1,2 @@
+// mop: lines 2 code 1 comment 1 blank 0
+fn mid() {}
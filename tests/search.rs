@@ -0,0 +1,133 @@
+// Without a terminal, the results are printed once and nothing is asked, so
+// the runs are checked through their output and their exit status
+use std::{
+    fs,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// one directory per run, the tests running in parallel
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+// The exit status and the output of mop, run on a directory with a main.rs file
+fn run(subcommand: &str, options: &[&str], content: &str) -> (Option<i32>, String) {
+    let run = RUNS.fetch_add(1, Ordering::Relaxed);
+    let directory = std::env::temp_dir().join(format!("mop-search-{}-{}", std::process::id(), run));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("main.rs"), content).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_mop"))
+        .arg(subcommand)
+        .args(options)
+        .arg(&directory)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap().replace(&directory.display().to_string(), "DIR");
+    (output.status.code(), stdout)
+}
+
+fn search(options: &[&str], content: &str) -> Option<i32> {
+    run("search", options, content).0
+}
+
+#[test]
+fn results_without_a_terminal() {
+    let (code, output) = run("search", &["foo"], "let foo = 1;\nlet bar = foo;\n");
+    assert_eq!(code, Some(0));
+    assert_eq!(output, "Searching for foo\n\n1) DIR/main.rs\n   1) [1] : let foo = 1;\n   2) [2] : let bar = foo;\n");
+    let (code, output) = run("todo", &[], "fn main() {\n    // TODO remove\n}\n");
+    assert_eq!(code, Some(0));
+    assert_eq!(output, "Searching for TODO\n\n1) DIR/main.rs\n   1) [2] : remove\n");
+}
+
+#[test]
+fn filters_apply_to_the_search_and_the_todos() {
+    assert_eq!(search(&["--lang", "Rust", "foo"], "let foo = 1;\n"), Some(0));
+    assert_eq!(search(&["--lang", "Python", "foo"], "let foo = 1;\n"), Some(1));
+    assert_eq!(search(&["--exclude", "*.rs", "foo"], "let foo = 1;\n"), Some(1));
+    assert_eq!(run("todo", &["--include", "main.*"], "// TODO remove\n").0, Some(0));
+    assert_eq!(run("todo", &["--include", "*.py"], "// TODO remove\n").0, Some(1));
+}
+
+#[test]
+fn case_and_words() {
+    assert_eq!(search(&["FOO"], "let foo = 1;\n"), Some(1));
+    assert_eq!(search(&["-S", "Foo"], "let foo = 1;\n"), Some(1));
+    assert_eq!(search(&["-w", "foo"], "let foobar = 1;\n"), Some(1));
+}
+
+#[test]
+fn words_starting_or_ending_with_a_punctuation() {
+    assert_eq!(search(&["-w", "-e", r"foo\("], "let x = foo();\n"), Some(0));
+    assert_eq!(search(&["-w", "-F", "-e", "foo("], "let x = foo();\n"), Some(0));
+    assert_eq!(search(&["-w", "-F", "-e", "foo("], "let x = barfoo();\n"), Some(1));
+    assert_eq!(search(&["-w", "-F", "--regex=->"], "fn f() -> u8 {}\n"), Some(0));
+    assert_eq!(search(&["-w", "-F", "--regex=->"], "fn f()->u8 {}\n"), Some(1));
+    let (code, output) = run("search", &["-w", "-F", "-e", "(x"], "f (x) (xy)\n");
+    assert_eq!(code, Some(0));
+    assert!(output.ends_with("main.rs\n   1) [1] : f (x) (xy)\n"), "{}", output);
+}
+
+#[test]
+fn regex_and_fixed_strings() {
+    assert_eq!(search(&["f.o"], "let foo = 1;\n"), Some(1));
+    assert_eq!(search(&["-e", "f.o", "-F"], "let foo = 1;\n"), Some(1));
+    assert_eq!(search(&["-e", "x.y("], "x.y(z)\n"), Some(2));
+}
+
+#[test]
+fn context_options() {
+    assert_eq!(search(&["-C", "2", "FOO"], "let foo = 1;\n"), Some(1));
+    assert_eq!(search(&["-A", "1", "-B", "3", "FOO"], "let foo = 1;\n"), Some(1));
+    assert_eq!(search(&["-C", "two", "foo"], "let foo = 1;\n"), Some(2));
+    let (code, output) = run("search", &["-C", "1", "foo"], "a\nlet foo = 1;\nb\nc\nd\nfoo();\n");
+    assert_eq!(code, Some(0));
+    assert!(output.ends_with("main.rs\n      [1] - a\n   1) [2] : let foo = 1;\n      [3] - b\n   --\n      [5] - d\n   2) [6] : foo();\n"), "{}", output);
+}
+
+#[test]
+fn several_patterns() {
+    assert_eq!(search(&["-e", "unwrap", "-e", "expect", "--not", "unwrap_or"], "a.unwrap_or(1);\n"), Some(1));
+    assert_eq!(search(&["-e", "unwrap", "--and", "panic"], "a.unwrap();\n"), Some(1));
+    assert_eq!(search(&["-e", "unwrap", "--not", "("], "a.unwrap();\n"), Some(2));
+    assert_eq!(search(&["-f", "/nonexistent/patterns.txt"], "a.unwrap();\n"), Some(2));
+    // PATTERN is literal, but not the --and and --not patterns, unless -F is given
+    assert_eq!(search(&["foo", "--and", "b.r"], "foo(bar);\n"), Some(0));
+    assert_eq!(search(&["-F", "foo", "--and", "b.r"], "foo(bar);\n"), Some(1));
+    assert_eq!(search(&["f.o", "--not", "b.r"], "f.o(bar);\n"), Some(1));
+}
+
+#[test]
+fn smart_case_for_each_list_of_patterns() {
+    assert_eq!(search(&["-S", "-e", "foo", "--and", "BAR"], "foo(bar);\n"), Some(1));
+    assert_eq!(search(&["-S", "-e", "foo", "--and", "BAR"], "FOO(BAR);\n"), Some(0));
+    assert_eq!(search(&["-S", "-e", "Foo", "--and", "bar"], "Foo(BAR);\n"), Some(0));
+    assert_eq!(search(&["-S", "-e", "foo", "--not", "Bar"], "foo(bar);\n"), Some(0));
+    assert_eq!(search(&["-S", "-e", "foo", "--not", "bar"], "foo(Bar);\n"), Some(1));
+}
+
+#[test]
+fn smart_case_skips_the_escapes() {
+    // an escaped backslash followed by an uppercase letter
+    assert_eq!(search(&["-S", "-e", r"\\Foo"], "\\foo\n"), Some(1));
+    assert_eq!(search(&["-S", "-e", r"\\Foo"], "\\Foo\n"), Some(0));
+    assert_eq!(search(&["-S", "-F", "-e", r"\Foo"], "\\foo\n"), Some(1));
+    // the letters of the escapes are not uppercase
+    assert_eq!(search(&["-S", "-e", r"\Sfoo"], "xFOO\n"), Some(0));
+    assert_eq!(search(&["-S", "-e", r"\p{Greek}\x{41}b"], "\u{3b1}aB\n"), Some(0));
+}
+
+#[test]
+fn multiline() {
+    let content = "unsafe {\n    transmute(x)\n}\n";
+    assert_eq!(search(&["-e", r"unsafe \{\s*transmute"], content), Some(1));
+    // --and applies to the text of the match, which doesn't go past the brace
+    assert_eq!(search(&["-U", "-e", r"unsafe \{", "--and", "transmute"], content), Some(1));
+    let (code, output) = run("search", &["-U", "-e", r"unsafe \{\s*transmute"], content);
+    assert_eq!(code, Some(0));
+    assert!(output.ends_with("main.rs\n   1) [1-2] : unsafe {\n"), "{}", output);
+    // a match starting on the line break of a CRLF file
+    let (code, output) = run("search", &["-U", "-e", r"\nfoo"], "let foo = 1;\r\nfoo\r\n");
+    assert_eq!(code, Some(0));
+    assert!(output.ends_with("main.rs\n   1) [1-2] : let foo = 1;\n"), "{}", output);
+}